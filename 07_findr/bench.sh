@@ -0,0 +1,40 @@
+#!/usr/bin/env bash
+
+# Times the walk on a few tree shapes with 1, 4 and 8 threads. Run this
+# before changing how --threads walks or what it defaults to; the walk
+# with more threads should be at least as fast on every shape.
+
+set -eu
+
+PRG="${PRG:-target/release/findr}"
+FILES="${FILES:-200000}"
+
+[[ -x "$PRG" ]] || cargo build --release
+DIR=$(mktemp -d)
+trap 'rm -rf "$DIR"' EXIT
+
+# Everything in one directory
+mkdir "$DIR/flat"
+(cd "$DIR/flat" && seq "$FILES" | xargs touch)
+
+# Everything under a single directory two levels down
+mkdir -p "$DIR/narrow/a/b"
+(cd "$DIR/narrow/a/b" && seq "$FILES" | xargs touch)
+
+# Spread over 100 directories of 100 subdirectories each
+for i in $(seq 100); do
+    for j in $(seq 100); do
+        mkdir -p "$DIR/wide/$i/$j"
+    done
+done
+(cd "$DIR/wide" && for d in */*; do
+    touch "$d"/{1..20}
+done)
+
+TIMEFORMAT="%R s real  %U s user"
+for shape in flat narrow wide; do
+    for threads in 1 4 8; do
+        printf "%-6s -j%s  " "$shape" "$threads"
+        time "$PRG" "$DIR/$shape" -j "$threads" > /dev/null
+    done
+done
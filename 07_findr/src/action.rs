@@ -0,0 +1,551 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{platform, walk::Entry, EntryType, MyResult};
+use chrono::{DateTime, Local};
+
+/// Stay well below the kernel's argument limit when batching `-exec ... +`.
+const MAX_BATCH_BYTES: usize = 128 * 1024;
+
+#[derive(Debug)]
+pub enum Action {
+    Print {
+        terminator: u8,
+    },
+    Printf(Vec<Directive>),
+    /// `failed` is set once an entry couldn't be deleted.
+    Delete {
+        failed: AtomicBool,
+    },
+    Exec(Exec),
+}
+
+impl Action {
+    /// Runs the action for one entry and returns its truth value.
+    pub fn run(&self, entry: &Entry, out: &mut dyn Write) -> MyResult<bool> {
+        match self {
+            Self::Print { terminator } => {
+                out.write_all(&platform::bytes(entry.path().as_os_str()))?;
+                out.write_all(&[*terminator])?;
+                Ok(true)
+            }
+            Self::Printf(directives) => {
+                for directive in directives {
+                    directive.write(entry, out)?;
+                }
+                Ok(true)
+            }
+            Self::Delete { failed } => {
+                let deleted = delete(entry);
+                if !deleted {
+                    failed.store(true, Ordering::Relaxed);
+                }
+                Ok(deleted)
+            }
+            Self::Exec(exec) => exec.run(entry, out),
+        }
+    }
+
+    /// Runs whatever is still queued once the walk is done. Returns false if
+    /// a batched command failed, a command couldn't be started or an entry
+    /// couldn't be deleted.
+    pub fn finish(&self, out: &mut dyn Write) -> MyResult<bool> {
+        match self {
+            Self::Delete { failed } => Ok(!failed.load(Ordering::Relaxed)),
+            Self::Exec(exec) => exec.finish(out),
+            _ => Ok(true),
+        }
+    }
+}
+
+fn delete(entry: &Entry) -> bool {
+    // Never delete the "." starting point itself.
+    if entry.depth() == 0 && entry.path() == Path::new(".") {
+        return true;
+    }
+    let res = if entry.file_type().is_dir() {
+        fs::remove_dir(entry.path())
+    } else {
+        fs::remove_file(entry.path())
+    };
+    match res {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("cannot delete {}: {}", entry.path().display(), e);
+            false
+        }
+    }
+}
+
+// --------------------------------------------------
+/// `-exec`/`-execdir`, either once per entry (`;`) or batched (`+`).
+#[derive(Debug)]
+pub struct Exec {
+    command: Vec<String>,
+    in_dir: bool,
+    batch: Option<Mutex<Batch>>,
+    /// Set once the command couldn't be started.
+    failed: AtomicBool,
+}
+
+#[derive(Debug, Default)]
+struct Batch {
+    dir: Option<PathBuf>,
+    args: Vec<OsString>,
+    bytes: usize,
+    failed: bool,
+}
+
+impl Exec {
+    /// `tokens` are the words after `-exec` up to, but not including, the
+    /// terminating `;` or `+`.
+    pub fn new(tokens: &[String], in_dir: bool, batched: bool) -> MyResult<Self> {
+        let flag = if in_dir { "-execdir" } else { "-exec" };
+        let mut command = tokens.to_vec();
+        if batched {
+            match command.pop().as_deref() {
+                Some("{}") => {}
+                _ => {
+                    return Err(From::from(format!(
+                        "{} ... + requires \"{{}}\" immediately before \"+\"",
+                        flag
+                    )))
+                }
+            }
+        }
+        if command.is_empty() {
+            return Err(From::from(format!("Missing command for \"{}\"", flag)));
+        }
+        Ok(Self {
+            command,
+            in_dir,
+            batch: batched.then(Mutex::default),
+            failed: AtomicBool::new(false),
+        })
+    }
+
+    /// The path handed to the command, and the directory to run it in.
+    fn target(&self, entry: &Entry) -> (OsString, Option<PathBuf>) {
+        if self.in_dir {
+            let dir = entry
+                .path()
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new("."));
+            let mut name = OsString::from("./");
+            name.push(entry.file_name());
+            (name, Some(dir.to_path_buf()))
+        } else {
+            (entry.path().as_os_str().to_owned(), None)
+        }
+    }
+
+    fn run(&self, entry: &Entry, out: &mut dyn Write) -> MyResult<bool> {
+        let (arg, dir) = self.target(entry);
+        let batch = match &self.batch {
+            None => {
+                let args = self.command[1..]
+                    .iter()
+                    .map(|word| replace_braces(word, &arg));
+                return self.spawn(args, dir.as_deref(), out);
+            }
+            Some(batch) => batch,
+        };
+
+        let mut batch = batch.lock().unwrap();
+        if !batch.args.is_empty() && (batch.dir != dir || batch.bytes + arg.len() > MAX_BATCH_BYTES)
+        {
+            self.flush(&mut batch, out)?;
+        }
+        batch.dir = dir;
+        batch.bytes += arg.len() + 1;
+        batch.args.push(arg);
+        Ok(true)
+    }
+
+    fn flush(&self, batch: &mut Batch, out: &mut dyn Write) -> MyResult<()> {
+        let args = self.command[1..]
+            .iter()
+            .map(OsString::from)
+            .chain(batch.args.drain(..));
+        if !self.spawn(args, batch.dir.as_deref(), out)? {
+            batch.failed = true;
+        }
+        batch.bytes = 0;
+        Ok(())
+    }
+
+    fn finish(&self, out: &mut dyn Write) -> MyResult<bool> {
+        if let Some(batch) = &self.batch {
+            let mut batch = batch.lock().unwrap();
+            if !batch.args.is_empty() {
+                self.flush(&mut batch, out)?;
+            }
+            if batch.failed {
+                return Ok(false);
+            }
+        }
+        Ok(!self.failed.load(Ordering::Relaxed))
+    }
+
+    /// Runs the command to completion; true if it exited successfully.
+    fn spawn(
+        &self,
+        args: impl Iterator<Item = OsString>,
+        dir: Option<&Path>,
+        out: &mut dyn Write,
+    ) -> MyResult<bool> {
+        // Keep our own buffered output ahead of anything the child prints.
+        out.flush()?;
+        let mut cmd = Command::new(&self.command[0]);
+        cmd.args(args);
+        if let Some(dir) = dir {
+            cmd.current_dir(dir);
+        }
+        match cmd.status() {
+            Ok(status) => Ok(status.success()),
+            Err(e) => {
+                eprintln!("{}: {}", self.command[0], e);
+                self.failed.store(true, Ordering::Relaxed);
+                Ok(false)
+            }
+        }
+    }
+}
+
+fn replace_braces(word: &str, path: &OsStr) -> OsString {
+    let mut parts = word.split("{}");
+    let mut arg = OsString::from(parts.next().unwrap_or_default());
+    for part in parts {
+        arg.push(path);
+        arg.push(part);
+    }
+    arg
+}
+
+// --------------------------------------------------
+/// One piece of a `-printf` format.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Directive {
+    Literal(Vec<u8>),
+    Field {
+        spec: char,
+        time: Option<char>,
+        left: bool,
+        width: usize,
+    },
+}
+
+impl Directive {
+    /// Parses a `-printf` format into literal text and `%` fields.
+    pub fn parse(format: &str) -> MyResult<Vec<Self>> {
+        let invalid = |what: &str| format!("Invalid -printf {} in \"{}\"", what, format);
+        let mut directives = vec![];
+        let mut literal = vec![];
+        let mut chars = format.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('n') => literal.push(b'\n'),
+                    Some('t') => literal.push(b'\t'),
+                    Some('r') => literal.push(b'\r'),
+                    Some('0') => literal.push(0),
+                    Some('a') => literal.push(7),
+                    Some('v') => literal.push(11),
+                    Some('f') => literal.push(12),
+                    Some('\\') => literal.push(b'\\'),
+                    Some(other) => {
+                        literal.push(b'\\');
+                        push_char(&mut literal, other);
+                    }
+                    None => literal.push(b'\\'),
+                },
+                '%' => {
+                    if chars.peek() == Some(&'%') {
+                        chars.next();
+                        literal.push(b'%');
+                        continue;
+                    }
+                    let left = chars.next_if_eq(&'-').is_some();
+                    let mut width = 0;
+                    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                        width = width * 10 + digit as usize;
+                        chars.next();
+                    }
+                    let spec = chars.next().ok_or_else(|| invalid("trailing \"%\""))?;
+                    let time = match spec {
+                        'A' | 'C' | 'T' => Some(
+                            chars
+                                .next()
+                                .filter(|c| *c == '@' || c.is_ascii_alphabetic())
+                                .ok_or_else(|| invalid(&format!("directive \"%{}\"", spec)))?,
+                        ),
+                        'a' | 'c' | 'd' | 'f' | 'g' | 'G' | 'h' | 'H' | 'i' | 'k' | 'b' | 'l'
+                        | 'm' | 'M' | 'n' | 'p' | 'P' | 's' | 't' | 'u' | 'U' | 'y' => None,
+                        _ => return Err(From::from(invalid(&format!("directive \"%{}\"", spec)))),
+                    };
+                    if !literal.is_empty() {
+                        directives.push(Self::Literal(std::mem::take(&mut literal)));
+                    }
+                    directives.push(Self::Field {
+                        spec,
+                        time,
+                        left,
+                        width,
+                    });
+                }
+                _ => push_char(&mut literal, c),
+            }
+        }
+        if !literal.is_empty() {
+            directives.push(Self::Literal(literal));
+        }
+        Ok(directives)
+    }
+
+    fn write(&self, entry: &Entry, out: &mut dyn Write) -> MyResult<()> {
+        let (spec, time, left, width) = match self {
+            Self::Literal(bytes) => return Ok(out.write_all(bytes)?),
+            Self::Field {
+                spec,
+                time,
+                left,
+                width,
+            } => (*spec, *time, *left, *width),
+        };
+        let value = field(entry, spec, time);
+        let pad = width.saturating_sub(String::from_utf8_lossy(&value).chars().count());
+        if !left {
+            out.write_all(&b" ".repeat(pad))?;
+        }
+        out.write_all(&value)?;
+        if left {
+            out.write_all(&b" ".repeat(pad))?;
+        }
+        Ok(())
+    }
+}
+
+fn push_char(bytes: &mut Vec<u8>, c: char) {
+    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+/// The bytes for one `%` field. Metadata that can't be read prints as empty.
+fn field(entry: &Entry, spec: char, time: Option<char>) -> Vec<u8> {
+    let path = entry.path();
+    let depth = entry.depth();
+    // The starting point is whatever is left after removing `depth`
+    // components from the end of the path.
+    let start = path.ancestors().nth(depth).unwrap_or(path);
+
+    let bytes = |s: &OsStr| platform::bytes(s).into_owned();
+    let text = |s: String| s.into_bytes();
+
+    match spec {
+        'p' => return bytes(path.as_os_str()),
+        'f' => return bytes(entry.file_name()),
+        'h' => {
+            return match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => bytes(parent.as_os_str()),
+                _ => b".".to_vec(),
+            }
+        }
+        'H' => return bytes(start.as_os_str()),
+        'P' => {
+            return path
+                .strip_prefix(start)
+                .map(|rel| bytes(rel.as_os_str()))
+                .unwrap_or_default()
+        }
+        'd' => return text(depth.to_string()),
+        'y' => return vec![type_char(entry)],
+        'l' => {
+            return if entry.path_is_symlink() {
+                fs::read_link(path)
+                    .map(|target| bytes(target.as_os_str()))
+                    .unwrap_or_default()
+            } else {
+                vec![]
+            }
+        }
+        _ => {}
+    }
+
+    let metadata = match entry.metadata() {
+        Ok(metadata) => metadata,
+        Err(_) => return vec![],
+    };
+    match spec {
+        's' => text(metadata.len().to_string()),
+        'k' => text(platform::blocks(&metadata).div_ceil(2).to_string()),
+        'b' => text(platform::blocks(&metadata).to_string()),
+        'm' => text(format!("{:o}", platform::mode(&metadata))),
+        'M' => text(format_mode(type_char(entry), platform::mode(&metadata))),
+        'n' => text(platform::nlink(&metadata).to_string()),
+        'i' => platform::ino(&metadata).map_or(vec![], |ino| text(ino.to_string())),
+        'U' => platform::uid(&metadata).map_or(vec![], |uid| text(uid.to_string())),
+        'G' => platform::gid(&metadata).map_or(vec![], |gid| text(gid.to_string())),
+        'u' => platform::uid(&metadata).map_or(vec![], |uid| {
+            text(platform::user_name(uid).unwrap_or_else(|| uid.to_string()))
+        }),
+        'g' => platform::gid(&metadata).map_or(vec![], |gid| {
+            text(platform::group_name(gid).unwrap_or_else(|| gid.to_string()))
+        }),
+        'a' => text(format_time(metadata.accessed().ok(), None)),
+        't' => text(format_time(metadata.modified().ok(), None)),
+        'c' => text(format_time(platform::ctime(&metadata), None)),
+        'A' => text(format_time(metadata.accessed().ok(), time)),
+        'T' => text(format_time(metadata.modified().ok(), time)),
+        'C' => text(format_time(platform::ctime(&metadata), time)),
+        _ => unreachable!("Invalid -printf directive"),
+    }
+}
+
+/// `None` gives the ctime(3) layout; `@` gives seconds since the epoch;
+/// any other letter is passed to strftime.
+fn format_time(time: Option<SystemTime>, fmt: Option<char>) -> String {
+    let time = match time {
+        Some(time) => time,
+        None => return String::new(),
+    };
+    match fmt {
+        Some('@') => {
+            let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+            format!("{}.{:09}0", since.as_secs(), since.subsec_nanos())
+        }
+        Some(letter) => DateTime::<Local>::from(time)
+            .format(&format!("%{}", letter))
+            .to_string(),
+        None => DateTime::<Local>::from(time)
+            .format("%a %b %e %H:%M:%S %Y")
+            .to_string(),
+    }
+}
+
+fn type_char(entry: &Entry) -> u8 {
+    EntryType::of(entry.file_type()).letter()
+}
+
+/// Formats permissions the way `ls -l` does, e.g. `drwxr-xr-x`.
+fn format_mode(type_char: u8, mode: u32) -> String {
+    let type_char = match type_char {
+        b'f' => '-',
+        other => other as char,
+    };
+    let bits = [
+        (0o400, 'r'),
+        (0o200, 'w'),
+        (0o100, 'x'),
+        (0o040, 'r'),
+        (0o020, 'w'),
+        (0o010, 'x'),
+        (0o004, 'r'),
+        (0o002, 'w'),
+        (0o001, 'x'),
+    ];
+    std::iter::once(type_char)
+        .chain(
+            bits.iter()
+                .map(|(mask, c)| if mode & mask != 0 { *c } else { '-' }),
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_mode, replace_braces, Directive, Exec};
+    use std::ffi::{OsStr, OsString};
+
+    #[test]
+    fn test_parse_directives() {
+        assert_eq!(
+            Directive::parse("%p\\n").unwrap(),
+            vec![
+                Directive::Field {
+                    spec: 'p',
+                    time: None,
+                    left: false,
+                    width: 0
+                },
+                Directive::Literal(b"\n".to_vec()),
+            ]
+        );
+
+        assert_eq!(
+            Directive::parse("100%% %-8f|%T@\\0").unwrap(),
+            vec![
+                Directive::Literal(b"100% ".to_vec()),
+                Directive::Field {
+                    spec: 'f',
+                    time: None,
+                    left: true,
+                    width: 8
+                },
+                Directive::Literal(b"|".to_vec()),
+                Directive::Field {
+                    spec: 'T',
+                    time: Some('@'),
+                    left: false,
+                    width: 0
+                },
+                Directive::Literal(vec![0]),
+            ]
+        );
+
+        let res = Directive::parse("%q");
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Invalid -printf directive \"%q\" in \"%q\""
+        );
+
+        let res = Directive::parse("%T");
+        assert!(res.is_err());
+
+        let res = Directive::parse("50%");
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_replace_braces() {
+        let path = OsStr::new("a/b.txt");
+        assert_eq!(replace_braces("{}", path), OsString::from("a/b.txt"));
+        assert_eq!(
+            replace_braces("x{}.bak", path),
+            OsString::from("xa/b.txt.bak")
+        );
+        assert_eq!(
+            replace_braces("{}{}", path),
+            OsString::from("a/b.txta/b.txt")
+        );
+        assert_eq!(replace_braces("echo", path), OsString::from("echo"));
+    }
+
+    #[test]
+    fn test_new_exec() {
+        let words = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+        assert!(Exec::new(&words("echo {}"), false, false).is_ok());
+        assert!(Exec::new(&words("echo {}"), false, true).is_ok());
+        assert!(Exec::new(&words("echo {} x"), false, true).is_err());
+        assert!(Exec::new(&words(""), true, false).is_err());
+        assert!(Exec::new(&words("{}"), false, true).is_err());
+    }
+
+    #[test]
+    fn test_format_mode() {
+        assert_eq!(format_mode(b'f', 0o755), "-rwxr-xr-x");
+        assert_eq!(format_mode(b'd', 0o750), "drwxr-x---");
+        assert_eq!(format_mode(b'l', 0o777), "lrwxrwxrwx");
+        assert_eq!(format_mode(b'f', 0o600), "-rw-------");
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+};
+
+use crate::{walk::Entry, MyResult};
+use regex::bytes::Regex;
+
+/// How much of a file the text/binary and magic checks look at.
+const BLOCK_BYTES: u64 = 8 * 1024;
+
+/// Longer lines are matched in pieces, so a file without newlines is never
+/// read in whole.
+const MAX_LINE_BYTES: u64 = 64 * 1024;
+
+/// How much of one piece of a long line is matched again with the next.
+const OVERLAP_BYTES: usize = 1024;
+
+/// Tests that have to open the file, and so only ever match regular files.
+/// Files that can't be read never match.
+#[derive(Debug)]
+pub enum ContentFilter {
+    Contains(Regex),
+    Text,
+    Binary,
+    Magic(Magic),
+}
+
+impl ContentFilter {
+    /// Parses the argument to the test named by `flag`. `val` is ignored by
+    /// the tests that take no argument.
+    pub fn parse(flag: &str, val: &str) -> MyResult<Self> {
+        let filter = match flag.trim_start_matches('-') {
+            "contains" => Regex::new(val).ok().map(Self::Contains),
+            "text-only" | "text" => Some(Self::Text),
+            "binary-only" | "binary" => Some(Self::Binary),
+            "magic" => Magic::parse(val).map(Self::Magic),
+            _ => unreachable!("Invalid content filter"),
+        };
+        filter.ok_or_else(|| From::from(format!("Invalid {} \"{}\"", flag, val)))
+    }
+
+    pub fn matches(&self, entry: &Entry) -> bool {
+        if !entry.file_type().is_file() {
+            return false;
+        }
+        let path = entry.path();
+        match self {
+            Self::Contains(re) => contains(path, re).unwrap_or(false),
+            Self::Text => first_block(path).is_ok_and(|block| !block.contains(&0)),
+            Self::Binary => first_block(path).is_ok_and(|block| block.contains(&0)),
+            Self::Magic(magic) => first_block(path).is_ok_and(|block| magic.matches(&block)),
+        }
+    }
+}
+
+/// Reads line by line and stops at the first match. Lines are matched
+/// without their newline, as grep does. Binary files never match.
+///
+/// Where a long line is cut, the pieces overlap by `OVERLAP_BYTES`, so only
+/// longer matches across the cut are missed.
+fn contains(path: &Path, re: &Regex) -> io::Result<bool> {
+    let mut file = BufReader::with_capacity(BLOCK_BYTES as usize, File::open(path)?);
+    if file.fill_buf()?.contains(&0) {
+        return Ok(false);
+    }
+    let mut line = vec![];
+    loop {
+        if (&mut file)
+            .take(MAX_LINE_BYTES)
+            .read_until(b'\n', &mut line)?
+            == 0
+        {
+            return Ok(false);
+        }
+        let cut = line.last() != Some(&b'\n');
+        if re.is_match(line.strip_suffix(b"\n").unwrap_or(&line)) {
+            return Ok(true);
+        }
+        if cut {
+            line.drain(..line.len().saturating_sub(OVERLAP_BYTES));
+        } else {
+            line.clear();
+        }
+    }
+}
+
+fn first_block(path: &Path) -> io::Result<Vec<u8>> {
+    let mut block = vec![];
+    File::open(path)?
+        .take(BLOCK_BYTES)
+        .read_to_end(&mut block)?;
+    Ok(block)
+}
+
+/// File formats `--magic` recognizes by their leading bytes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Magic {
+    Elf,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+    Zip,
+    Tar,
+    Pdf,
+    Png,
+    Jpeg,
+    Gif,
+    Mp3,
+    Sqlite,
+    Wasm,
+    Script,
+}
+
+impl Magic {
+    pub const NAMES: [&'static str; 15] = [
+        "elf", "gzip", "bzip2", "xz", "zstd", "zip", "tar", "pdf", "png", "jpeg", "gif", "mp3",
+        "sqlite", "wasm", "script",
+    ];
+
+    pub fn parse(val: &str) -> Option<Self> {
+        match val {
+            "elf" => Some(Self::Elf),
+            "gzip" => Some(Self::Gzip),
+            "bzip2" => Some(Self::Bzip2),
+            "xz" => Some(Self::Xz),
+            "zstd" => Some(Self::Zstd),
+            "zip" => Some(Self::Zip),
+            "tar" => Some(Self::Tar),
+            "pdf" => Some(Self::Pdf),
+            "png" => Some(Self::Png),
+            "jpeg" => Some(Self::Jpeg),
+            "gif" => Some(Self::Gif),
+            "mp3" => Some(Self::Mp3),
+            "sqlite" => Some(Self::Sqlite),
+            "wasm" => Some(Self::Wasm),
+            "script" => Some(Self::Script),
+            _ => None,
+        }
+    }
+
+    fn matches(self, block: &[u8]) -> bool {
+        let starts = |prefix: &[u8]| block.starts_with(prefix);
+        match self {
+            Self::Elf => starts(b"\x7fELF"),
+            Self::Gzip => starts(b"\x1f\x8b"),
+            Self::Bzip2 => starts(b"BZh"),
+            Self::Xz => starts(b"\xfd7zXZ\0"),
+            Self::Zstd => starts(b"\x28\xb5\x2f\xfd"),
+            Self::Zip => starts(b"PK\x03\x04") || starts(b"PK\x05\x06"),
+            Self::Tar => block.get(257..262) == Some(b"ustar"),
+            Self::Pdf => starts(b"%PDF-"),
+            Self::Png => starts(b"\x89PNG\r\n\x1a\n"),
+            Self::Jpeg => starts(b"\xff\xd8\xff"),
+            Self::Gif => starts(b"GIF87a") || starts(b"GIF89a"),
+            Self::Mp3 => {
+                starts(b"ID3")
+                    || [b"\xff\xfb", b"\xff\xf3", b"\xff\xf2"]
+                        .iter()
+                        .any(|p| starts(*p))
+            }
+            Self::Sqlite => starts(b"SQLite format 3\0"),
+            Self::Wasm => starts(b"\0asm"),
+            Self::Script => starts(b"#!"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContentFilter, Magic};
+
+    #[test]
+    fn test_parse() {
+        assert!(ContentFilter::parse("--contains", "[a-").is_err());
+        assert!(ContentFilter::parse("-contains", "fo+").is_ok());
+        assert!(ContentFilter::parse("--magic", "doc").is_err());
+        assert!(matches!(
+            ContentFilter::parse("-magic", "png"),
+            Ok(ContentFilter::Magic(Magic::Png))
+        ));
+        assert!(matches!(
+            ContentFilter::parse("--binary-only", ""),
+            Ok(ContentFilter::Binary)
+        ));
+        for name in Magic::NAMES {
+            assert!(Magic::parse(name).is_some());
+        }
+    }
+
+    #[test]
+    fn test_magic() {
+        assert!(Magic::Png.matches(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        assert!(!Magic::Png.matches(b"PNG"));
+        assert!(Magic::Gif.matches(b"GIF89a\x01\0"));
+        assert!(Magic::Mp3.matches(b"ID3\x03"));
+        assert!(Magic::Elf.matches(b"\x7fELF\x02\x01"));
+        assert!(Magic::Script.matches(b"#!/bin/sh\n"));
+
+        let mut tar = vec![0; 512];
+        tar[257..263].copy_from_slice(b"ustar\0");
+        assert!(Magic::Tar.matches(&tar));
+        assert!(!Magic::Tar.matches(&tar[..260]));
+    }
+}
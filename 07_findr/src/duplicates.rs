@@ -0,0 +1,187 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{platform, walk::Entry, MyResult};
+use sha2::{Digest, Sha256};
+
+/// Bytes hashed to tell apart files of the same size before reading them
+/// in full.
+const PARTIAL_BYTES: u64 = 4 * 1024;
+
+#[derive(Debug)]
+pub struct DuplicateOptions {
+    /// Report hard links to the same file as duplicates of each other.
+    pub hardlinks: bool,
+    /// Follow the sets with a count of the duplicates and the bytes they use.
+    pub summary: bool,
+}
+
+/// A file found by the walk, with every path that links to it.
+#[derive(Debug)]
+struct Inode {
+    size: u64,
+    paths: Vec<PathBuf>,
+}
+
+/// The nonempty regular files that matched, collected by the walkers and
+/// keyed by the file they are.
+#[derive(Debug, Default)]
+pub struct Candidates {
+    inodes: Mutex<HashMap<platform::FileId, Inode>>,
+}
+
+impl Candidates {
+    pub fn add(&self, entry: &Entry) {
+        let metadata = match entry.metadata() {
+            Ok(metadata) if metadata.is_file() && metadata.len() > 0 => metadata,
+            _ => return,
+        };
+        let mut inodes = self.inodes.lock().unwrap();
+        inodes
+            .entry(platform::file_id(&metadata, entry.path()))
+            .or_insert_with(|| Inode {
+                size: metadata.len(),
+                paths: vec![],
+            })
+            .paths
+            .push(entry.path().to_path_buf());
+    }
+
+    /// Writes each set of identical files as a block of paths, one per
+    /// line, with a blank line between sets.
+    ///
+    /// Files are grouped by size, then by a hash of their first
+    /// `PARTIAL_BYTES`, and only files that still collide are hashed in
+    /// full. Hard links share their data, so they never count as wasted
+    /// space. Unless `hardlinks` is set, only the first path of each file
+    /// is considered, so links to the same file aren't duplicates.
+    pub fn report(self, options: &DuplicateOptions, out: &mut dyn Write) -> MyResult<()> {
+        let mut by_size: HashMap<u64, Vec<Inode>> = HashMap::new();
+        for mut inode in self.inodes.into_inner().unwrap().into_values() {
+            inode.paths.sort();
+            if !options.hardlinks {
+                inode.paths.truncate(1);
+            }
+            by_size.entry(inode.size).or_default().push(inode);
+        }
+
+        let mut sets = vec![];
+        for (size, inodes) in by_size {
+            for group in group_by(inodes, |inode| hash(&inode.paths[0], PARTIAL_BYTES)) {
+                if size <= PARTIAL_BYTES {
+                    sets.push(group);
+                } else {
+                    sets.extend(group_by(group, |inode| hash(&inode.paths[0], size)));
+                }
+            }
+        }
+
+        // Each set's paths, and the bytes taken by all but one of its files.
+        let mut sets: Vec<(Vec<PathBuf>, u64)> = sets
+            .into_iter()
+            .map(|set| {
+                let wasted = set[0].size * (set.len() as u64 - 1);
+                let mut paths: Vec<PathBuf> =
+                    set.into_iter().flat_map(|inode| inode.paths).collect();
+                paths.sort();
+                (paths, wasted)
+            })
+            .collect();
+        sets.sort();
+
+        for (i, (paths, _)) in sets.iter().enumerate() {
+            if i > 0 {
+                out.write_all(b"\n")?;
+            }
+            for path in paths {
+                out.write_all(&platform::bytes(path.as_os_str()))?;
+                out.write_all(b"\n")?;
+            }
+        }
+
+        if options.summary {
+            let files: usize = sets.iter().map(|(paths, _)| paths.len() - 1).sum();
+            let wasted: u64 = sets.iter().map(|(_, wasted)| wasted).sum();
+            if !sets.is_empty() {
+                out.write_all(b"\n")?;
+            }
+            writeln!(
+                out,
+                "{} duplicate files in {} sets, {} bytes wasted",
+                files,
+                sets.len(),
+                wasted
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits `inodes` by the key of each, dropping groups with only one path
+/// between them. Files that can't be read are reported and left out.
+fn group_by(inodes: Vec<Inode>, key: impl Fn(&Inode) -> io::Result<[u8; 32]>) -> Vec<Vec<Inode>> {
+    let paths = |group: &[Inode]| group.iter().map(|inode| inode.paths.len()).sum::<usize>();
+    if inodes.len() < 2 {
+        // Nothing to tell apart, so there is no need to read the file.
+        return if paths(&inodes) > 1 {
+            vec![inodes]
+        } else {
+            vec![]
+        };
+    }
+    let mut groups: HashMap<[u8; 32], Vec<Inode>> = HashMap::new();
+    for inode in inodes {
+        match key(&inode) {
+            Ok(key) => groups.entry(key).or_default().push(inode),
+            Err(e) => eprintln!("{}: {}", inode.paths[0].display(), e),
+        }
+    }
+    groups
+        .into_values()
+        .filter(|group| paths(group) > 1)
+        .collect()
+}
+
+/// Hashes the first `limit` bytes of the file at `path`.
+fn hash(path: &Path, limit: u64) -> io::Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?.take(limit), &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{group_by, Inode};
+    use std::{io, path::PathBuf};
+
+    fn inode(size: u64, path: &str) -> Inode {
+        Inode {
+            size,
+            paths: vec![PathBuf::from(path)],
+        }
+    }
+
+    #[test]
+    fn test_group_by() {
+        let inodes = vec![inode(1, "a"), inode(2, "b"), inode(1, "c"), inode(3, "d")];
+        let mut groups = group_by(inodes, |inode| Ok([inode.size as u8; 32]));
+        assert_eq!(groups.len(), 1);
+        let paths: Vec<_> = groups.remove(0).into_iter().map(|i| i.paths).collect();
+        assert_eq!(paths, [[PathBuf::from("a")], [PathBuf::from("c")]]);
+
+        // A lone file isn't hashed at all.
+        assert!(group_by(vec![inode(1, "a")], |_| unreachable!()).is_empty());
+        let mut linked = inode(1, "a");
+        linked.paths.push(PathBuf::from("b"));
+        let groups = group_by(vec![linked], |_| unreachable!());
+        assert_eq!(groups.len(), 1);
+
+        let inodes = vec![inode(1, "a"), inode(1, "b")];
+        assert!(group_by(inodes, |_| Err(io::ErrorKind::Other.into())).is_empty());
+    }
+}
@@ -0,0 +1,508 @@
+use std::{io::Write, sync::atomic::AtomicBool, time::SystemTime};
+
+use clap::{Arg, Command};
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    action::{Action, Directive, Exec},
+    content::ContentFilter,
+    filter::Filter,
+    glob,
+    walk::Entry,
+    EntryType, MyResult,
+};
+
+/// Tokens that start an expression when they appear on the command line.
+const PRIMARIES: &[&str] = &[
+    "(",
+    "!",
+    "-not",
+    "-true",
+    "-false",
+    "-name",
+    "-iname",
+    "-path",
+    "-ipath",
+    "-wholename",
+    "-iwholename",
+    "-regex",
+    "-iregex",
+    "-type",
+    "-xtype",
+    "-size",
+    "-mtime",
+    "-mmin",
+    "-newer",
+    "-empty",
+    "-perm",
+    "-user",
+    "-group",
+    "-contains",
+    "-text",
+    "-binary",
+    "-magic",
+    "-print",
+    "-print0",
+    "-printf",
+    "-delete",
+    "-exec",
+    "-execdir",
+];
+
+/// A find-style boolean expression over directory entries.
+#[derive(Debug)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Bool(bool),
+    /// Matches the whole file name.
+    Name(Regex),
+    /// Matches the whole path.
+    Path(Regex),
+    Type(Vec<EntryType>),
+    XType(Vec<EntryType>),
+    Filter(Filter),
+    Content(ContentFilter),
+    Action(Action),
+}
+
+impl Expr {
+    /// Joins `exprs` with `-and`, or gives `-true` if there are none.
+    pub fn all(exprs: impl IntoIterator<Item = Expr>) -> Self {
+        Self::fold(exprs, Self::And).unwrap_or(Self::Bool(true))
+    }
+
+    /// Joins `exprs` with `-or`, or gives `-true` if there are none.
+    pub fn any(exprs: impl IntoIterator<Item = Expr>) -> Self {
+        Self::fold(exprs, Self::Or).unwrap_or(Self::Bool(true))
+    }
+
+    /// Builds a name or path test from the pattern given to `flag`.
+    /// `name` and `path` take globs, while `regex` takes a regular
+    /// expression; the `i` forms ignore case.
+    pub fn pattern(flag: &str, val: &str) -> MyResult<Self> {
+        let invalid = || format!("Invalid {} \"{}\"", flag, val);
+        let name = flag.trim_start_matches('-');
+        let case_insensitive = name.starts_with('i');
+        match name.trim_start_matches('i') {
+            "name" => glob::compile(val, case_insensitive)
+                .map(Self::Name)
+                .ok_or_else(|| From::from(invalid())),
+            "path" | "wholename" => glob::compile(val, case_insensitive)
+                .map(Self::Path)
+                .ok_or_else(|| From::from(invalid())),
+            "regex" => RegexBuilder::new(&format!("^(?:{})$", val))
+                .case_insensitive(case_insensitive)
+                .build()
+                .map(Self::Path)
+                .map_err(|_| From::from(invalid())),
+            _ => unreachable!("Invalid pattern"),
+        }
+    }
+
+    fn fold(
+        exprs: impl IntoIterator<Item = Expr>,
+        join: fn(Box<Expr>, Box<Expr>) -> Expr,
+    ) -> Option<Self> {
+        exprs
+            .into_iter()
+            .reduce(|lhs, rhs| join(Box::new(lhs), Box::new(rhs)))
+    }
+
+    /// Evaluates left to right, skipping the right-hand side of `-and` and
+    /// `-or` once the result is known. Actions write to `out`; only a failed
+    /// write is an error.
+    pub fn eval(&self, entry: &Entry, out: &mut dyn Write) -> MyResult<bool> {
+        Ok(match self {
+            Self::And(lhs, rhs) => lhs.eval(entry, out)? && rhs.eval(entry, out)?,
+            Self::Or(lhs, rhs) => lhs.eval(entry, out)? || rhs.eval(entry, out)?,
+            Self::Not(expr) => !expr.eval(entry, out)?,
+            Self::Bool(val) => *val,
+            Self::Name(re) => re.is_match(&entry.file_name().to_string_lossy()),
+            Self::Path(re) => re.is_match(&entry.path().to_string_lossy()),
+            Self::Type(entry_types) => entry_types.iter().any(|t| t.matches(entry)),
+            Self::XType(entry_types) => entry_types.iter().any(|t| t.matches_target(entry)),
+            Self::Filter(filter) => filter.matches(entry),
+            Self::Content(filter) => filter.matches(entry),
+            Self::Action(action) => action.run(entry, out)?,
+        })
+    }
+
+    /// Completes batched actions after the walk; false if any of them failed.
+    pub fn finish(&self, out: &mut dyn Write) -> MyResult<bool> {
+        Ok(match self {
+            Self::And(lhs, rhs) | Self::Or(lhs, rhs) => lhs.finish(out)? & rhs.finish(out)?,
+            Self::Not(expr) => expr.finish(out)?,
+            Self::Action(action) => action.finish(out)?,
+            _ => true,
+        })
+    }
+
+    /// True if `pred` holds for any node of the tree.
+    pub fn contains(&self, pred: &dyn Fn(&Expr) -> bool) -> bool {
+        pred(self)
+            || match self {
+                Self::And(lhs, rhs) | Self::Or(lhs, rhs) => {
+                    lhs.contains(pred) || rhs.contains(pred)
+                }
+                Self::Not(expr) => expr.contains(pred),
+                _ => false,
+            }
+    }
+}
+
+/// Index of the first argument that begins an expression, if any. The
+/// value of one of `cli`'s options is never taken for a primary, so
+/// `--name '('` still looks for a file named "(".
+pub fn find_start(args: &[String], cli: &Command) -> Option<usize> {
+    let mut i = 1;
+    while i < args.len() {
+        if PRIMARIES.contains(&args[i].as_str()) {
+            return Some(i);
+        }
+        i += if takes_value(cli, &args[i]) { 2 } else { 1 };
+    }
+    None
+}
+
+/// Whether `arg` is an option of `cli` whose value is the next argument.
+fn takes_value(cli: &Command, arg: &str) -> bool {
+    let takes_values = |opt: &Arg| opt.get_action().takes_values();
+    if let Some(long) = arg.strip_prefix("--") {
+        !long.contains('=')
+            && cli.get_arguments().any(|opt| {
+                takes_values(opt)
+                    && (opt.get_long() == Some(long)
+                        || opt
+                            .get_all_aliases()
+                            .is_some_and(|aliases| aliases.contains(&long)))
+            })
+    } else if let Some(shorts) = arg.strip_prefix('-') {
+        // In a cluster such as `-et`, the first option that takes a value
+        // takes the rest of the cluster, or else the next argument.
+        shorts
+            .char_indices()
+            .find_map(|(i, c)| {
+                cli.get_arguments()
+                    .find(|opt| opt.get_short() == Some(c))
+                    .filter(|opt| takes_values(opt))
+                    .map(|_| i + c.len_utf8() == shorts.len())
+            })
+            .unwrap_or(false)
+    } else {
+        false
+    }
+}
+
+/// Parses the tokens of an expression such as
+/// `( -name a -o -name b ) -not -type d`.
+///
+/// Precedence from high to low is `( )`, `-not`, `-and` (or juxtaposition)
+/// and `-or`.
+///
+/// Most tests may also be given as options, so `-size +1k` here is
+/// `--size +1k` on the command line. Either way the test is built by the
+/// same function, which is handed the flag as spelled for its messages.
+pub fn parse(tokens: &[String], now: SystemTime) -> MyResult<Expr> {
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        now,
+    };
+    let expr = parser.parse_or()?;
+    match parser.next() {
+        None => Ok(expr),
+        Some(")") => Err(From::from("Unexpected \")\" in expression")),
+        Some(tok) => Err(From::from(format!("Unexpected \"{}\" in expression", tok))),
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [String],
+    pos: usize,
+    now: SystemTime,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let tok = self.peek();
+        self.pos += 1;
+        tok
+    }
+
+    fn parse_or(&mut self) -> MyResult<Expr> {
+        let mut lhs = self.parse_and()?;
+        while let Some("-o" | "-or") = self.peek() {
+            self.next();
+            let rhs = self.parse_and()?;
+            lhs = Expr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> MyResult<Expr> {
+        let mut lhs = self.parse_not()?;
+        loop {
+            match self.peek() {
+                Some("-a" | "-and") => {
+                    self.next();
+                }
+                None | Some(")" | "-o" | "-or") => return Ok(lhs),
+                _ => {}
+            }
+            let rhs = self.parse_not()?;
+            lhs = Expr::And(Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn parse_not(&mut self) -> MyResult<Expr> {
+        match self.peek() {
+            Some("!" | "-not") => {
+                self.next();
+                Ok(Expr::Not(Box::new(self.parse_not()?)))
+            }
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> MyResult<Expr> {
+        let tok = match self.next() {
+            Some(tok) => tok,
+            None => return Err(From::from("Expected an expression")),
+        };
+        match tok {
+            "(" => {
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(")") => Ok(expr),
+                    _ => Err(From::from("Missing \")\" in expression")),
+                }
+            }
+            "-true" => Ok(Expr::Bool(true)),
+            "-false" => Ok(Expr::Bool(false)),
+            "-empty" => Ok(Expr::Filter(Filter::Empty)),
+            "-name" | "-iname" | "-path" | "-ipath" | "-wholename" | "-iwholename" | "-regex"
+            | "-iregex" => {
+                let val = self.arg(tok)?;
+                Expr::pattern(tok, val)
+            }
+            "-type" | "-xtype" => {
+                let val = self.arg(tok)?;
+                let types = val
+                    .split(',')
+                    .map(|t| {
+                        EntryType::parse(t).ok_or_else(|| format!("Invalid {} \"{}\"", tok, val))
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(if tok == "-type" {
+                    Expr::Type(types)
+                } else {
+                    Expr::XType(types)
+                })
+            }
+            "-size" | "-mtime" | "-mmin" | "-newer" | "-perm" | "-user" | "-group" => {
+                let val = self.arg(tok)?;
+                Filter::parse(tok, val, self.now).map(Expr::Filter)
+            }
+            "-contains" | "-magic" => {
+                let val = self.arg(tok)?;
+                ContentFilter::parse(tok, val).map(Expr::Content)
+            }
+            "-text" | "-binary" => ContentFilter::parse(tok, "").map(Expr::Content),
+            "-print" => Ok(Expr::Action(Action::Print { terminator: b'\n' })),
+            "-print0" => Ok(Expr::Action(Action::Print { terminator: 0 })),
+            "-printf" => {
+                let val = self.arg(tok)?;
+                Directive::parse(val).map(|format| Expr::Action(Action::Printf(format)))
+            }
+            "-delete" => Ok(Expr::Action(Action::Delete {
+                failed: AtomicBool::new(false),
+            })),
+            "-exec" | "-execdir" => {
+                let start = self.pos;
+                loop {
+                    match self.next() {
+                        None => {
+                            return Err(From::from(format!(
+                                "Missing \";\" or \"+\" after \"{}\"",
+                                tok
+                            )))
+                        }
+                        Some(";") => break,
+                        Some("+") if self.tokens[self.pos - 2] == "{}" => break,
+                        _ => {}
+                    }
+                }
+                let words = &self.tokens[start..self.pos - 1];
+                let batched = self.tokens[self.pos - 1] == "+";
+                Exec::new(words, tok == "-execdir", batched)
+                    .map(|exec| Expr::Action(Action::Exec(exec)))
+            }
+            ")" => Err(From::from("Unexpected \")\" in expression")),
+            "-a" | "-and" | "-o" | "-or" => Err(From::from(format!(
+                "Expected an expression before \"{}\"",
+                tok
+            ))),
+            _ => Err(From::from(format!("Unknown expression \"{}\"", tok))),
+        }
+    }
+
+    fn arg(&mut self, tok: &str) -> MyResult<&'a str> {
+        self.next()
+            .ok_or_else(|| From::from(format!("Missing argument to \"{}\"", tok)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_start, parse, Entry, Expr};
+    use clap::{Arg, ArgAction, Command};
+    use std::{io, time::SystemTime};
+    use walkdir::WalkDir;
+
+    fn entry(path: &str) -> Entry {
+        WalkDir::new(path)
+            .into_iter()
+            .next()
+            .unwrap()
+            .unwrap()
+            .into()
+    }
+
+    fn eval(expr: &str, path: &str) -> bool {
+        let tokens: Vec<_> = expr.split_whitespace().map(String::from).collect();
+        parse(&tokens, SystemTime::now())
+            .unwrap()
+            .eval(&entry(path), &mut io::sink())
+            .unwrap()
+    }
+
+    fn parse_err(expr: &str) -> String {
+        let tokens: Vec<_> = expr.split_whitespace().map(String::from).collect();
+        parse(&tokens, SystemTime::now()).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_find_start() {
+        let cli = Command::new("findr")
+            .arg(Arg::new("paths").action(ArgAction::Append))
+            .arg(Arg::new("name").short('n').long("name").visible_alias("nm"))
+            .arg(
+                Arg::new("empty")
+                    .short('e')
+                    .long("empty")
+                    .action(ArgAction::SetTrue),
+            );
+        let start = |s: &str| {
+            let args: Vec<_> = s.split_whitespace().map(String::from).collect();
+            find_start(&args, &cli)
+        };
+        assert_eq!(start("findr"), None);
+        assert_eq!(start("findr . -n f"), None);
+        assert_eq!(start("findr . -name a"), Some(2));
+        assert_eq!(start("findr a b ( -true )"), Some(3));
+        assert_eq!(start("findr ! -empty"), Some(1));
+        // Option values are skipped, however the option is spelled
+        assert_eq!(start("findr . --name ( -true"), Some(4));
+        assert_eq!(start("findr . --nm ! -true"), Some(4));
+        assert_eq!(start("findr . -en ! -true"), Some(4));
+        assert_eq!(start("findr . --name=x ! -true"), Some(3));
+        assert_eq!(start("findr . -n! -true"), Some(3));
+        assert_eq!(start("findr . --empty ! -true"), Some(3));
+    }
+
+    #[test]
+    fn test_precedence() {
+        let csv = "tests/inputs/g.csv";
+
+        // -and binds tighter than -or
+        assert!(eval("-true -o -false -a -false", csv));
+        assert!(!eval("( -true -o -false ) -a -false", csv));
+
+        // Juxtaposition is an implicit -and
+        assert!(!eval("-true -false", csv));
+        assert!(eval("-true ! -false", csv));
+
+        // -not binds tighter than -and
+        assert!(eval("-not -false -and -true", csv));
+        assert!(!eval("-not ( -false -or -true )", csv));
+        assert!(eval("! ! -true", csv));
+    }
+
+    #[test]
+    fn test_leaves() {
+        assert!(eval("-name *.csv -type f", "tests/inputs/g.csv"));
+        assert!(!eval("-name csv", "tests/inputs/g.csv"));
+        assert!(eval("-iname G.CSV", "tests/inputs/g.csv"));
+        assert!(eval("-path */inputs/g*", "tests/inputs/g.csv"));
+        assert!(!eval("-path inputs/g*", "tests/inputs/g.csv"));
+        assert!(eval("-wholename tests/*", "tests/inputs/g.csv"));
+        assert!(eval("-ipath TESTS/*", "tests/inputs/g.csv"));
+        assert!(eval("-regex .*/g\\.csv", "tests/inputs/g.csv"));
+        assert!(!eval("-regex g\\.csv", "tests/inputs/g.csv"));
+        assert!(eval("-iregex .*G.CSV", "tests/inputs/g.csv"));
+        assert!(eval("-type l,d", "tests/inputs/a"));
+        assert!(eval("-size 2c -not -empty", "tests/inputs/g.csv"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_err(""), "Expected an expression");
+        assert_eq!(parse_err("( -true"), "Missing \")\" in expression");
+        assert_eq!(parse_err("-true )"), "Unexpected \")\" in expression");
+        assert_eq!(
+            parse_err("-o -true"),
+            "Expected an expression before \"-o\""
+        );
+        assert_eq!(parse_err("-true -o"), "Expected an expression");
+        assert_eq!(parse_err("-name"), "Missing argument to \"-name\"");
+        assert_eq!(parse_err("-name [a"), "Invalid -name \"[a\"");
+        assert_eq!(parse_err("-regex *a"), "Invalid -regex \"*a\"");
+        assert_eq!(parse_err("-type x"), "Invalid -type \"x\"");
+        assert_eq!(parse_err("-size 1q"), "Invalid -size \"1q\"");
+        assert_eq!(parse_err("-bogus"), "Unknown expression \"-bogus\"");
+    }
+
+    #[test]
+    fn test_combine() {
+        let dir = entry("tests/inputs");
+        let expr = Expr::all(vec![]);
+        assert!(expr.eval(&dir, &mut io::sink()).unwrap());
+
+        let expr = Expr::any(vec![Expr::Bool(false), Expr::Bool(true)]);
+        assert!(expr.eval(&dir, &mut io::sink()).unwrap());
+
+        let expr = Expr::all(vec![Expr::Bool(true), Expr::Bool(false)]);
+        assert!(!expr.eval(&dir, &mut io::sink()).unwrap());
+    }
+
+    #[test]
+    fn test_actions() {
+        let tokens: Vec<_> = "-name *.csv -print -o -printf %f!"
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        let expr = parse(&tokens, SystemTime::now()).unwrap();
+
+        // Short-circuiting decides which action runs
+        let mut out = vec![];
+        expr.eval(&entry("tests/inputs/g.csv"), &mut out).unwrap();
+        expr.eval(&entry("tests/inputs/a"), &mut out).unwrap();
+        assert_eq!(out, b"tests/inputs/g.csv\na!");
+
+        assert_eq!(
+            parse_err("-exec echo {}"),
+            "Missing \";\" or \"+\" after \"-exec\""
+        );
+        assert_eq!(parse_err("-exec ;"), "Missing command for \"-exec\"");
+        assert_eq!(
+            parse_err("-exec echo {} x +"),
+            "Missing \";\" or \"+\" after \"-exec\""
+        );
+    }
+}
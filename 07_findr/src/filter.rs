@@ -0,0 +1,304 @@
+use std::{
+    fs::{self, Metadata},
+    time::SystemTime,
+};
+
+use crate::{platform, walk::Entry, MyResult};
+
+const DAY: i64 = 24 * 60 * 60;
+const MINUTE: i64 = 60;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Comparison {
+    Less,
+    Equal,
+    Greater,
+}
+
+impl Comparison {
+    /// Splits a leading `+` (greater than) or `-` (less than) off a value.
+    fn split(val: &str) -> (Self, &str) {
+        match val.as_bytes().first() {
+            Some(b'+') => (Self::Greater, &val[1..]),
+            Some(b'-') => (Self::Less, &val[1..]),
+            _ => (Self::Equal, val),
+        }
+    }
+
+    fn test(self, actual: i64, wanted: i64) -> bool {
+        match self {
+            Self::Less => actual < wanted,
+            Self::Equal => actual == wanted,
+            Self::Greater => actual > wanted,
+        }
+    }
+}
+
+/// Size in `unit`s, rounded up the same way `find -size` does.
+#[derive(Debug, Eq, PartialEq)]
+pub struct SizeFilter {
+    cmp: Comparison,
+    amount: i64,
+    unit: u64,
+}
+
+impl SizeFilter {
+    fn parse(val: &str) -> Option<Self> {
+        let (cmp, rest) = Comparison::split(val);
+        let suffix_len = usize::from(rest.ends_with(|c: char| c.is_ascii_alphabetic()));
+        let (digits, suffix) = rest.split_at(rest.len() - suffix_len);
+        let unit = match suffix {
+            "c" => 1,
+            "w" => 2,
+            "k" => 1024,
+            "M" => 1024 * 1024,
+            "G" => 1024 * 1024 * 1024,
+            "b" | "" => 512,
+            _ => return None,
+        };
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let amount = digits.parse().ok()?;
+        Some(Self { cmp, amount, unit })
+    }
+
+    fn matches(&self, metadata: &Metadata) -> bool {
+        let units = metadata.len().div_ceil(self.unit);
+        i64::try_from(units).map_or(self.cmp == Comparison::Greater, |units| {
+            self.cmp.test(units, self.amount)
+        })
+    }
+}
+
+/// Age of the last modification, truncated to whole `unit`s.
+#[derive(Debug, Eq, PartialEq)]
+pub struct TimeFilter {
+    cmp: Comparison,
+    amount: i64,
+    unit: i64,
+    now: SystemTime,
+}
+
+impl TimeFilter {
+    fn parse(val: &str, unit: i64, now: SystemTime) -> Option<Self> {
+        let (cmp, rest) = Comparison::split(val);
+        if !rest.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let amount = rest.parse().ok()?;
+        Some(Self {
+            cmp,
+            amount,
+            unit,
+            now,
+        })
+    }
+
+    fn matches(&self, metadata: &Metadata) -> bool {
+        metadata.modified().is_ok_and(|modified| {
+            let age = match self.now.duration_since(modified) {
+                Ok(age) => age.as_secs() as i64,
+                Err(e) => -(e.duration().as_secs() as i64),
+            };
+            self.cmp.test(age.div_euclid(self.unit), self.amount)
+        })
+    }
+}
+
+/// Modified more recently than a reference file.
+#[derive(Debug, Eq, PartialEq)]
+pub struct NewerFilter {
+    reference: SystemTime,
+}
+
+impl NewerFilter {
+    fn parse(filename: &str) -> MyResult<Self> {
+        let reference = fs::metadata(filename)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| format!("{}: {}", filename, e))?;
+        Ok(Self { reference })
+    }
+
+    fn matches(&self, metadata: &Metadata) -> bool {
+        metadata
+            .modified()
+            .is_ok_and(|modified| modified > self.reference)
+    }
+}
+
+/// Permission bits: `MODE` exactly, `-MODE` all of, or `/MODE` any of.
+#[derive(Debug, Eq, PartialEq)]
+pub enum PermFilter {
+    Exact(u32),
+    All(u32),
+    Any(u32),
+}
+
+impl PermFilter {
+    fn parse(val: &str) -> Option<Self> {
+        let (make, mode): (fn(u32) -> Self, _) = match val.as_bytes().first() {
+            Some(b'-') => (Self::All, &val[1..]),
+            Some(b'/') => (Self::Any, &val[1..]),
+            _ => (Self::Exact, val),
+        };
+        if mode.is_empty() || mode.len() > 4 {
+            return None;
+        }
+        u32::from_str_radix(mode, 8).ok().map(make)
+    }
+
+    fn matches(&self, metadata: &Metadata) -> bool {
+        let mode = platform::mode(metadata);
+        match *self {
+            Self::Exact(bits) => mode == bits,
+            Self::All(bits) => mode & bits == bits,
+            Self::Any(bits) => bits == 0 || mode & bits != 0,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Filter {
+    Size(SizeFilter),
+    Time(TimeFilter),
+    Newer(NewerFilter),
+    Perm(PermFilter),
+    User(u32),
+    Group(u32),
+    Empty,
+}
+
+impl Filter {
+    /// Parses the argument to the test named by `flag`.
+    pub fn parse(flag: &str, val: &str, now: SystemTime) -> MyResult<Self> {
+        let filter = match flag.trim_start_matches('-') {
+            "size" => SizeFilter::parse(val).map(Self::Size),
+            "mtime" => TimeFilter::parse(val, DAY, now).map(Self::Time),
+            "mmin" => TimeFilter::parse(val, MINUTE, now).map(Self::Time),
+            "newer" => return NewerFilter::parse(val).map(Self::Newer),
+            "perm" => PermFilter::parse(val).map(Self::Perm),
+            "user" => val
+                .parse()
+                .ok()
+                .or_else(|| platform::uid_by_name(val))
+                .map(Self::User),
+            "group" => val
+                .parse()
+                .ok()
+                .or_else(|| platform::gid_by_name(val))
+                .map(Self::Group),
+            _ => unreachable!("Invalid filter"),
+        };
+        filter.ok_or_else(|| From::from(format!("Invalid {} \"{}\"", flag, val)))
+    }
+
+    /// Entries whose metadata can't be read never match.
+    pub fn matches(&self, entry: &Entry) -> bool {
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => return false,
+        };
+        match self {
+            Self::Size(size) => size.matches(&metadata),
+            Self::Time(time) => time.matches(&metadata),
+            Self::Newer(newer) => newer.matches(&metadata),
+            Self::Perm(perm) => perm.matches(&metadata),
+            Self::User(uid) => platform::uid(&metadata) == Some(*uid),
+            Self::Group(gid) => platform::gid(&metadata) == Some(*gid),
+            Self::Empty => {
+                if metadata.is_dir() {
+                    fs::read_dir(entry.path()).is_ok_and(|mut dir| dir.next().is_none())
+                } else {
+                    metadata.is_file() && metadata.len() == 0
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Comparison, Filter, PermFilter, SizeFilter, TimeFilter, DAY, MINUTE};
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_parse_size() {
+        assert!(SizeFilter::parse("").is_none());
+        assert!(SizeFilter::parse("+").is_none());
+        assert!(SizeFilter::parse("1x").is_none());
+        assert!(SizeFilter::parse("k").is_none());
+
+        let size = SizeFilter::parse("10").unwrap();
+        assert_eq!(
+            (size.cmp, size.amount, size.unit),
+            (Comparison::Equal, 10, 512)
+        );
+
+        let size = SizeFilter::parse("+2k").unwrap();
+        assert_eq!(
+            (size.cmp, size.amount, size.unit),
+            (Comparison::Greater, 2, 1024)
+        );
+
+        let size = SizeFilter::parse("-3M").unwrap();
+        assert_eq!(
+            (size.cmp, size.amount, size.unit),
+            (Comparison::Less, 3, 1024 * 1024)
+        );
+
+        let size = SizeFilter::parse("7c").unwrap();
+        assert_eq!(
+            (size.cmp, size.amount, size.unit),
+            (Comparison::Equal, 7, 1)
+        );
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let now = SystemTime::now();
+        assert!(TimeFilter::parse("", DAY, now).is_none());
+        assert!(TimeFilter::parse("1d", DAY, now).is_none());
+        assert!(TimeFilter::parse("+-1", DAY, now).is_none());
+
+        let time = TimeFilter::parse("-2", DAY, now).unwrap();
+        assert_eq!((time.cmp, time.amount), (Comparison::Less, 2));
+
+        let time = TimeFilter::parse("+30", MINUTE, now).unwrap();
+        assert_eq!(
+            (time.cmp, time.amount, time.unit),
+            (Comparison::Greater, 30, 60)
+        );
+    }
+
+    #[test]
+    fn test_parse_perm() {
+        assert!(PermFilter::parse("").is_none());
+        assert!(PermFilter::parse("-").is_none());
+        assert!(PermFilter::parse("8").is_none());
+        assert!(PermFilter::parse("12345").is_none());
+        assert!(PermFilter::parse("u+x").is_none());
+
+        assert_eq!(PermFilter::parse("644"), Some(PermFilter::Exact(0o644)));
+        assert_eq!(PermFilter::parse("-111"), Some(PermFilter::All(0o111)));
+        assert_eq!(PermFilter::parse("/022"), Some(PermFilter::Any(0o022)));
+        assert_eq!(PermFilter::parse("4755"), Some(PermFilter::Exact(0o4755)));
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        let now = SystemTime::now();
+
+        let res = Filter::parse("--size", "1q", now);
+        assert_eq!(res.unwrap_err().to_string(), "Invalid --size \"1q\"");
+
+        let res = Filter::parse("-mmin", "x", now);
+        assert_eq!(res.unwrap_err().to_string(), "Invalid -mmin \"x\"");
+
+        let res = Filter::parse("-user", "0", now);
+        assert_eq!(res.unwrap(), Filter::User(0));
+
+        let res = Filter::parse("--group", "0", now);
+        assert_eq!(res.unwrap(), Filter::Group(0));
+    }
+}
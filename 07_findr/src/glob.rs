@@ -0,0 +1,145 @@
+use regex::{Regex, RegexBuilder};
+
+/// Compiles a shell glob into a regex that must match the whole input.
+///
+/// `*` matches any run of characters and `?` any single one, including `/`,
+/// as with `find -path`. `[...]` is a bracket expression, negated by a leading
+/// `!` or `^`, and a backslash makes the next character literal. Returns
+/// `None` for an unterminated bracket or a trailing backslash.
+pub fn compile(glob: &str, case_insensitive: bool) -> Option<Regex> {
+    RegexBuilder::new(&translate(glob)?)
+        .case_insensitive(case_insensitive)
+        .build()
+        .ok()
+}
+
+fn translate(glob: &str) -> Option<String> {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut re = String::from("(?s)^");
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            '\\' => {
+                i += 1;
+                re.push_str(&regex::escape(&chars.get(i)?.to_string()));
+            }
+            '[' => {
+                let (class, end) = translate_class(&chars, i + 1)?;
+                re.push_str(&class);
+                i = end;
+            }
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+
+    re.push('$');
+    Some(re)
+}
+
+/// Translates the bracket expression starting just after `[`, returning the
+/// regex class and the index of the closing `]`.
+fn translate_class(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let mut class = String::from("[");
+    let mut i = start;
+
+    if let Some('!' | '^') = chars.get(i) {
+        class.push('^');
+        i += 1;
+    }
+
+    let first = i;
+    loop {
+        match *chars.get(i)? {
+            // A "]" right after the opening bracket is literal.
+            ']' if i > first => break,
+            '[' if chars.get(i + 1) == Some(&':') => {
+                let len = chars[i..].windows(2).position(|w| w == [':', ']'])?;
+                class.extend(&chars[i..i + len + 2]);
+                i += len + 1;
+            }
+            '-' if i > first && chars.get(i + 1).is_some_and(|c| *c != ']') => class.push('-'),
+            '\\' => {
+                i += 1;
+                push_class_char(&mut class, *chars.get(i)?);
+            }
+            c => push_class_char(&mut class, c),
+        }
+        i += 1;
+    }
+
+    class.push(']');
+    Some((class, i))
+}
+
+fn push_class_char(class: &mut String, c: char) {
+    if !c.is_alphanumeric() {
+        class.push('\\');
+    }
+    class.push(c);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compile;
+
+    fn matches(glob: &str, text: &str) -> bool {
+        compile(glob, false).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert!(matches("*.txt", "a.txt"));
+        assert!(matches("*.txt", ".txt"));
+        assert!(!matches("*.txt", "a.txt.bak"));
+        assert!(!matches("txt", "txt.bak"));
+        assert!(matches("txt", "txt"));
+        assert!(matches("?.csv", "g.csv"));
+        assert!(!matches("?.csv", "gg.csv"));
+        assert!(matches("*/target/*", "./a/target/debug"));
+        assert!(matches("a*b", "a/x/b"));
+        assert!(matches("a.c", "a.c"));
+        assert!(!matches("a.c", "abc"));
+        assert!(matches("(x)+", "(x)+"));
+    }
+
+    #[test]
+    fn test_brackets() {
+        assert!(matches("[abc].txt", "b.txt"));
+        assert!(!matches("[abc].txt", "d.txt"));
+        assert!(matches("[a-c]", "b"));
+        assert!(!matches("[!a-c]", "b"));
+        assert!(matches("[^a-c]", "d"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("[!]]", "a"));
+        assert!(matches("[a-]", "-"));
+        assert!(matches("[[:digit:]]x", "7x"));
+        assert!(!matches("[[:digit:]]x", "ax"));
+        assert!(matches("[\\]]", "]"));
+        assert!(matches("[&~]", "~"));
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "a"));
+        assert!(matches("a\\?", "a?"));
+        assert!(matches("\\[a]", "[a]"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(compile("[abc", false).is_none());
+        assert!(compile("abc\\", false).is_none());
+        assert!(compile("[[:digit:]", false).is_none());
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        assert!(compile("*.CSV", true).unwrap().is_match("g.csv"));
+        assert!(!compile("*.CSV", false).unwrap().is_match("g.csv"));
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+
+use crate::walk::Entry;
+
+/// Per-directory ignore files, lowest precedence first.
+const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+
+/// Decides which entries `--ignore` skips: anything matched by the ignore
+/// files in the directories above the entry, up to the root of the git
+/// repository it is in, by that repository's `.git/info/exclude` or by the
+/// global git excludes file, plus hidden entries unless `--hidden` is given.
+/// `.git` directories are always skipped.
+///
+/// The ignore files of a directory are read the first time one of its
+/// entries is checked, so rules deeper in the tree override shallower ones
+/// the same way they do for git.
+#[derive(Debug)]
+pub struct IgnoreRules {
+    hidden: bool,
+    global: Gitignore,
+    dirs: Mutex<HashMap<PathBuf, Arc<Gitignore>>>,
+    outside: Mutex<HashMap<PathBuf, Arc<Outside>>>,
+}
+
+/// The rules that reach into a search path from the directories above it.
+/// Those only exist when the search path is inside a git repository.
+#[derive(Debug)]
+struct Outside {
+    /// The search path made absolute, so that entries below it can be
+    /// matched against rules from above it.
+    root: PathBuf,
+    /// The rules of each directory above the search path up to the root of
+    /// the repository, nearest first.
+    parents: Vec<Arc<Gitignore>>,
+    /// The repository's `.git/info/exclude`.
+    exclude: Gitignore,
+}
+
+impl IgnoreRules {
+    pub fn new(hidden: bool) -> Self {
+        let (global, err) = Gitignore::global();
+        if let Some(e) = err {
+            eprintln!("{}", e);
+        }
+        Self {
+            hidden,
+            global,
+            dirs: Mutex::default(),
+            outside: Mutex::default(),
+        }
+    }
+
+    /// True if `entry` should be skipped. Search paths themselves are never
+    /// skipped. Pruning an ignored directory normally keeps its contents
+    /// from being seen at all; when that isn't possible, e.g. when
+    /// directories come after their contents, `check_parents` makes the
+    /// directories between the search path and the entry count too.
+    pub fn is_ignored(&self, entry: &Entry, check_parents: bool) -> bool {
+        let path = entry.path();
+        let is_dir = entry.file_type().is_dir();
+        if entry.depth() == 0 {
+            return false;
+        }
+        if self.ignored(path, entry.depth(), is_dir) {
+            return true;
+        }
+        check_parents
+            && path
+                .ancestors()
+                .skip(1)
+                .zip((1..entry.depth()).rev())
+                .any(|(dir, depth)| self.ignored(dir, depth, true))
+    }
+
+    /// Checks one path at `depth` levels below its search path.
+    fn ignored(&self, path: &Path, depth: usize, is_dir: bool) -> bool {
+        // Every ancestor of a path is one level up, the search path last.
+        let Some(root) = path.ancestors().nth(depth) else {
+            return false;
+        };
+        let name = path.file_name().unwrap_or_default();
+        if is_dir && name == ".git" {
+            return true;
+        }
+        if !self.hidden && name.to_string_lossy().starts_with('.') {
+            return true;
+        }
+
+        // The closest directory with an opinion wins.
+        for dir in path.ancestors().skip(1).take(depth) {
+            match self.rules(dir).matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        let outside = self.outside(root);
+        let path = outside.root.join(path.strip_prefix(root).unwrap_or(path));
+        for rules in outside
+            .parents
+            .iter()
+            .map(Arc::as_ref)
+            .chain([&outside.exclude])
+        {
+            match rules.matched(&path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        self.global.matched(&path, is_dir).is_ignore()
+    }
+
+    fn outside(&self, root: &Path) -> Arc<Outside> {
+        let mut outside = self.outside.lock().unwrap();
+        if let Some(rules) = outside.get(root) {
+            return Arc::clone(rules);
+        }
+
+        let abs = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let repo = abs.ancestors().find(|dir| dir.join(".git").exists());
+        let mut rules = Outside {
+            root: abs.clone(),
+            parents: vec![],
+            exclude: Gitignore::empty(),
+        };
+        if let Some(repo) = repo {
+            if repo != abs {
+                for dir in abs.ancestors().skip(1) {
+                    rules.parents.push(self.rules(dir));
+                    if dir == repo {
+                        break;
+                    }
+                }
+            }
+            rules.exclude = build(repo, &[".git/info/exclude"]);
+        }
+        let rules = Arc::new(rules);
+        outside.insert(root.to_path_buf(), Arc::clone(&rules));
+        rules
+    }
+
+    fn rules(&self, dir: &Path) -> Arc<Gitignore> {
+        let mut dirs = self.dirs.lock().unwrap();
+        if let Some(rules) = dirs.get(dir) {
+            return Arc::clone(rules);
+        }
+
+        let rules = Arc::new(build(dir, IGNORE_FILES));
+        dirs.insert(dir.to_path_buf(), Arc::clone(&rules));
+        rules
+    }
+}
+
+/// Reads whichever of `files` exist in `dir` into one set of rules.
+fn build(dir: &Path, files: &[&str]) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(dir);
+    for file in files {
+        let path = dir.join(file);
+        if path.is_file() {
+            if let Some(e) = builder.add(&path) {
+                eprintln!("{}", e);
+            }
+        }
+    }
+    builder.build().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        Gitignore::empty()
+    })
+}
@@ -1,30 +1,248 @@
-use std::error::Error;
+mod action;
+mod content;
+mod duplicates;
+mod expr;
+mod filter;
+mod glob;
+mod ignore_rules;
+mod platform;
+mod walk;
 
-use clap::{Arg, ArgAction, Command};
-use regex::Regex;
-use walkdir::{DirEntry, WalkDir};
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    ffi::OsString,
+    fs::{self, FileType},
+    io::{self, BufWriter, Write},
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::Mutex,
+    thread,
+    time::SystemTime,
+};
+
+use action::Action;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use content::{ContentFilter, Magic};
+use duplicates::{Candidates, DuplicateOptions};
+use expr::Expr;
+use filter::Filter;
+use ignore_rules::IgnoreRules;
+use walk::Entry;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+const TYPES: [&str; 7] = ["b", "c", "d", "p", "f", "l", "s"];
+
 #[derive(Debug, Eq, PartialEq)]
 enum EntryType {
+    Block,
+    Char,
     Dir,
+    Fifo,
     File,
     Link,
+    Socket,
+}
+
+impl EntryType {
+    fn parse(val: &str) -> Option<Self> {
+        match val {
+            "b" => Some(Self::Block),
+            "c" => Some(Self::Char),
+            "d" => Some(Self::Dir),
+            "p" => Some(Self::Fifo),
+            "f" => Some(Self::File),
+            "l" => Some(Self::Link),
+            "s" => Some(Self::Socket),
+            _ => None,
+        }
+    }
+
+    fn of(file_type: FileType) -> Self {
+        if file_type.is_symlink() {
+            Self::Link
+        } else if file_type.is_dir() {
+            Self::Dir
+        } else {
+            platform::special_type(file_type).unwrap_or(Self::File)
+        }
+    }
+
+    /// The type of the entry a symlink points to, as for `-xtype`. Broken
+    /// links, and links themselves when they are being followed, are `Link`.
+    fn of_target(entry: &Entry) -> Self {
+        if !entry.path_is_symlink() {
+            Self::of(entry.file_type())
+        } else if entry.file_type().is_symlink() {
+            fs::metadata(entry.path()).map_or(Self::Link, |m| Self::of(m.file_type()))
+        } else {
+            Self::Link
+        }
+    }
+
+    /// The letter used by `-type` and `-printf %y`.
+    fn letter(&self) -> u8 {
+        match self {
+            Self::Block => b'b',
+            Self::Char => b'c',
+            Self::Dir => b'd',
+            Self::Fifo => b'p',
+            Self::File => b'f',
+            Self::Link => b'l',
+            Self::Socket => b's',
+        }
+    }
+
+    fn matches(&self, entry: &Entry) -> bool {
+        *self == Self::of(entry.file_type())
+    }
+
+    fn matches_target(&self, entry: &Entry) -> bool {
+        *self == Self::of_target(entry)
+    }
+}
+
+/// What `--sort` orders entries by. Ties are broken by path.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SortKey {
+    Name,
+    Path,
+    Mtime,
+    Size,
+}
+
+impl SortKey {
+    fn parse(val: &str) -> Option<Self> {
+        match val {
+            "name" => Some(Self::Name),
+            "path" => Some(Self::Path),
+            "mtime" => Some(Self::Mtime),
+            "size" => Some(Self::Size),
+            _ => None,
+        }
+    }
+
+    /// Sorts `entries` in place. A directory is kept after its contents
+    /// when `contents_first` is set, so -delete still works.
+    fn sort(self, entries: &mut Vec<Entry>, reverse: bool, contents_first: bool) {
+        let mut keyed: Vec<_> = entries.drain(..).map(|e| (self.value(&e), e)).collect();
+        keyed.sort_by(|(x, a), (y, b)| {
+            let order = x.cmp(y).then_with(|| a.path().cmp(b.path()));
+            if reverse {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+        let sorted = keyed.into_iter().map(|(_, e)| e).collect();
+        entries.extend(if contents_first {
+            contents_first_order(sorted)
+        } else {
+            sorted
+        });
+    }
+
+    /// Entries whose metadata can't be read sort first.
+    fn value(self, entry: &Entry) -> SortValue {
+        match self {
+            Self::Name => SortValue::Name(entry.file_name().to_os_string()),
+            Self::Path => SortValue::Path,
+            Self::Mtime => SortValue::Mtime(entry.metadata().ok().and_then(|m| m.modified().ok())),
+            Self::Size => SortValue::Size(entry.metadata().ok().map(|m| m.len())),
+        }
+    }
+}
+
+/// Moves each directory in `entries` to just after the last of its
+/// contents, leaving everything else in order.
+fn contents_first_order(entries: Vec<Entry>) -> Vec<Entry> {
+    // How many of each directory's contents haven't come yet
+    let mut pending: HashMap<PathBuf, usize> = entries
+        .iter()
+        .map(|e| (e.path().to_path_buf(), 0))
+        .collect();
+    for entry in &entries {
+        for dir in entry.path().ancestors().skip(1) {
+            if let Some(count) = pending.get_mut(dir) {
+                *count += 1;
+            }
+        }
+    }
+
+    let mut held: HashMap<PathBuf, Vec<Entry>> = HashMap::new();
+    let mut ordered = Vec::with_capacity(entries.len());
+    for entry in entries {
+        if pending[entry.path()] > 0 {
+            held.entry(entry.path().to_path_buf())
+                .or_default()
+                .push(entry);
+            continue;
+        }
+        let mut ready = vec![entry];
+        while let Some(entry) = ready.pop() {
+            for dir in entry.path().ancestors().skip(1) {
+                if let Some(count) = pending.get_mut(dir) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.extend(held.remove(dir).unwrap_or_default());
+                    }
+                }
+            }
+            ordered.push(entry);
+        }
+    }
+    ordered
+}
+
+#[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum SortValue {
+    Name(OsString),
+    Path,
+    Mtime(Option<SystemTime>),
+    Size(Option<u64>),
 }
 
 #[derive(Debug)]
 pub struct Config {
     paths: Vec<String>,
-    names: Vec<Regex>,
-    entry_types: Vec<EntryType>,
+    expr: Expr,
+    min_depth: usize,
+    max_depth: Option<usize>,
+    one_file_system: bool,
+    follow_links: bool,
+    ignore: Option<IgnoreRules>,
+    threads: usize,
+    sort: Option<SortKey>,
+    reverse: bool,
+    duplicates: Option<DuplicateOptions>,
 }
 
 pub fn get_args() -> MyResult<Config> {
-    let matches = Command::new("findr")
+    let cli = Command::new("findr")
         .version("0.1.0")
         .author("Tian Yu <gasnus@gmail.com>")
         .about("Rust find")
+        .override_usage("findr [OPTIONS] [PATH]... [EXPRESSION]")
+        .after_help(
+            "An EXPRESSION follows the paths and combines tests with \
+             \"(\" \")\", \"-not\", \"-and\" and \"-or\", e.g.\n  \
+             findr src \\( -name '*.rs' -o -name '*.toml' \\) -not -path '*/target/*'\n\n\
+             Tests: -name GLOB, -iname GLOB, -path GLOB, -ipath GLOB, \
+             -regex REGEX, -iregex REGEX, -type TYPE, -xtype TYPE, -size SIZE, \
+             -mtime DAYS, -mmin MINUTES, -newer FILE, -empty, -perm MODE, \
+             -user USER, -group GROUP, -contains REGEX, -text, -binary, \
+             -magic FORMAT, -true, -false\n\
+             TYPE is one or more of b, c, d, p, f, l and s, separated by \
+             commas in expressions.\n\
+             Actions: -print, -print0, -printf FORMAT, -delete, \
+             -exec COMMAND ;, -exec COMMAND {} +, -execdir COMMAND ;, \
+             -execdir COMMAND {} +\n\
+             Name, path and regex options are or-ed with each other; the \
+             options above are and-ed with the expression, and -print \
+             is added when it has no actions.",
+        )
         .arg(
             Arg::new("paths")
                 .value_name("PATH")
@@ -37,7 +255,36 @@ pub fn get_args() -> MyResult<Config> {
                 .value_name("NAME")
                 .short('n')
                 .long("name")
-                .help("Name")
+                .help("Name glob")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("inames")
+                .value_name("NAME")
+                .long("iname")
+                .help("Case-insensitive name glob")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("path_globs")
+                .value_name("PATH")
+                .long("path")
+                .visible_alias("wholename")
+                .help("Path glob")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("regexes")
+                .value_name("REGEX")
+                .long("regex")
+                .help("Regular expression matching the whole path")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("iregexes")
+                .value_name("REGEX")
+                .long("iregex")
+                .help("Case-insensitive regular expression matching the whole path")
                 .action(ArgAction::Append),
         )
         .arg(
@@ -46,34 +293,221 @@ pub fn get_args() -> MyResult<Config> {
                 .short('t')
                 .long("type")
                 .help("Entry type")
-                .value_parser(["f", "d", "l"])
+                .value_parser(TYPES)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("xtypes")
+                .value_name("TYPE")
+                .long("xtype")
+                .help("Entry type, or the type of a symlink's target")
+                .value_parser(TYPES)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("size")
+                .value_name("SIZE")
+                .long("size")
+                .help("File size, e.g. +10k or -2M")
+                .allow_hyphen_values(true)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("mtime")
+                .value_name("DAYS")
+                .long("mtime")
+                .help("Modified DAYS ago")
+                .allow_hyphen_values(true)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("mmin")
+                .value_name("MINUTES")
+                .long("mmin")
+                .help("Modified MINUTES ago")
+                .allow_hyphen_values(true)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("newer")
+                .value_name("FILE")
+                .long("newer")
+                .help("Modified more recently than FILE")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("empty")
+                .long("empty")
+                .help("Empty file or directory")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("perm")
+                .value_name("MODE")
+                .long("perm")
+                .help("Permission bits: MODE, -MODE (all) or /MODE (any)")
+                .allow_hyphen_values(true)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("user")
+                .value_name("USER")
+                .long("user")
+                .help("Owned by USER")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("group")
+                .value_name("GROUP")
+                .long("group")
+                .help("Owned by GROUP")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("contains")
+                .value_name("REGEX")
+                .long("contains")
+                .help("Text file contents match REGEX")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("text_only")
+                .long("text-only")
+                .help("Text files, with no NUL byte in the first block")
+                .conflicts_with("binary_only")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("binary_only")
+                .long("binary-only")
+                .help("Binary files, with a NUL byte in the first block")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("magic")
+                .value_name("FORMAT")
+                .long("magic")
+                .help("File format by its leading bytes")
+                .value_parser(Magic::NAMES)
                 .action(ArgAction::Append),
         )
-        .get_matches();
+        .arg(
+            Arg::new("max_depth")
+                .value_name("LEVELS")
+                .long("max-depth")
+                .visible_alias("maxdepth")
+                .help("Descend at most LEVELS below the search paths")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("min_depth")
+                .value_name("LEVELS")
+                .long("min-depth")
+                .visible_alias("mindepth")
+                .help("Ignore entries less than LEVELS below the search paths")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("one_file_system")
+                .long("one-file-system")
+                .visible_alias("xdev")
+                .help("Don't descend into other filesystems")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("follow")
+                .short('L')
+                .long("follow")
+                .help("Follow symbolic links")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("ignore")
+                .long("ignore")
+                .help("Skip .gitignore/.ignore matches, .git and hidden entries")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("hidden")
+                .long("hidden")
+                .help("Don't skip hidden entries with --ignore")
+                .requires("ignore")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("threads")
+                .value_name("N")
+                .short('j')
+                .long("threads")
+                .help("Walk with N threads [default: number of CPUs]")
+                .value_parser(clap::value_parser!(NonZeroUsize)),
+        )
+        .arg(
+            Arg::new("sort")
+                .value_name("KEY")
+                .long("sort")
+                .help("Process entries in KEY order instead of as they are found")
+                .value_parser(["name", "path", "mtime", "size"]),
+        )
+        .arg(
+            Arg::new("reverse")
+                .short('r')
+                .long("reverse")
+                .help("Reverse the --sort order")
+                .requires("sort")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("duplicates")
+                .long("duplicates")
+                .help("List sets of matching files with identical contents")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("hardlinks")
+                .long("hardlinks")
+                .help("With --duplicates, count hard links as duplicates")
+                .requires("duplicates")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("summary")
+                .long("summary")
+                .help("With --duplicates, total the duplicates and wasted bytes")
+                .requires("duplicates")
+                .action(ArgAction::SetTrue),
+        );
 
-    let names = matches
-        .get_many::<String>("names")
-        .map(|vals| {
-            vals.into_iter()
-                .map(|name| Regex::new(&name).map_err(|_| format!("Invalid --name \"{}\"", name)))
-                .collect::<Result<Vec<_>, _>>()
-        })
-        .transpose()?
+    let mut args: Vec<String> = env::args().collect();
+    let expression = expr::find_start(&args, &cli)
+        .map(|start| args.split_off(start))
         .unwrap_or_default();
+    let matches = cli.get_matches_from(args);
 
-    let entry_types = matches
-        .get_many::<String>("types")
-        .map(|vals| {
-            vals.into_iter()
-                .map(|val| match val.as_str() {
-                    "d" => EntryType::Dir,
-                    "f" => EntryType::File,
-                    "l" => EntryType::Link,
-                    _ => unreachable!("Invalid type"),
-                })
-                .collect()
-        })
-        .unwrap_or_default();
+    let now = SystemTime::now();
+
+    let mut names = vec![];
+    for (id, flag) in [
+        ("names", "--name"),
+        ("inames", "--iname"),
+        ("path_globs", "--path"),
+        ("regexes", "--regex"),
+        ("iregexes", "--iregex"),
+    ] {
+        for val in matches.get_many::<String>(id).into_iter().flatten() {
+            names.push(Expr::pattern(flag, val)?);
+        }
+    }
+
+    let entry_types = |id| {
+        matches
+            .get_many::<String>(id)
+            .into_iter()
+            .flatten()
+            .map(|val| EntryType::parse(val).expect("Invalid type"))
+            .collect::<Vec<_>>()
+    };
+    let (types, xtypes) = (entry_types("types"), entry_types("xtypes"));
 
     let paths = matches
         .get_many::<String>("paths")
@@ -81,50 +515,150 @@ pub fn get_args() -> MyResult<Config> {
         .map(|p| p.to_owned())
         .collect();
 
+    let mut exprs = get_filters(&matches, now)?;
+    if !names.is_empty() {
+        exprs.insert(0, Expr::any(names));
+    }
+    if !xtypes.is_empty() {
+        exprs.insert(0, Expr::XType(xtypes));
+    }
+    if !types.is_empty() {
+        exprs.insert(0, Expr::Type(types));
+    }
+    if !expression.is_empty() {
+        exprs.push(expr::parse(&expression, now)?);
+    }
+
+    let duplicates = matches.get_flag("duplicates").then(|| DuplicateOptions {
+        hardlinks: matches.get_flag("hardlinks"),
+        summary: matches.get_flag("summary"),
+    });
+
+    let mut expr = Expr::all(exprs);
+    let has_actions = expr.contains(&|e| matches!(e, Expr::Action(_)));
+    if duplicates.is_some() && has_actions {
+        return Err(From::from("--duplicates can't be used with actions"));
+    }
+    if duplicates.is_none() && !has_actions {
+        let print = Expr::Action(Action::Print { terminator: b'\n' });
+        expr = Expr::And(Box::new(expr), Box::new(print));
+    }
+
     Ok(Config {
         paths,
-        names,
-        entry_types,
+        expr,
+        min_depth: matches.get_one("min_depth").copied().unwrap_or(0),
+        max_depth: matches.get_one("max_depth").copied(),
+        one_file_system: matches.get_flag("one_file_system"),
+        follow_links: matches.get_flag("follow"),
+        ignore: matches
+            .get_flag("ignore")
+            .then(|| IgnoreRules::new(matches.get_flag("hidden"))),
+        threads: matches
+            .get_one("threads")
+            .copied()
+            .or_else(|| thread::available_parallelism().ok())
+            .map_or(1, NonZeroUsize::get),
+        sort: matches
+            .get_one::<String>("sort")
+            .map(|val| SortKey::parse(val).expect("Invalid sort key")),
+        reverse: matches.get_flag("reverse"),
+        duplicates,
     })
 }
 
-pub fn run(config: Config) -> MyResult<()> {
-    let type_filter = |entry: &DirEntry| {
-        config.entry_types.is_empty()
-            || config
-                .entry_types
-                .iter()
-                .any(|entry_type| match entry_type {
-                    EntryType::Link => entry.file_type().is_symlink(),
-                    EntryType::Dir => entry.file_type().is_dir(),
-                    EntryType::File => entry.file_type().is_file(),
-                })
-    };
+fn get_filters(matches: &ArgMatches, now: SystemTime) -> MyResult<Vec<Expr>> {
+    let mut exprs = vec![];
+    for id in ["size", "mtime", "mmin", "newer", "perm", "user", "group"] {
+        for val in matches.get_many::<String>(id).into_iter().flatten() {
+            let flag = format!("--{}", id);
+            exprs.push(Expr::Filter(Filter::parse(&flag, val, now)?));
+        }
+    }
+    if matches.get_flag("empty") {
+        exprs.push(Expr::Filter(Filter::Empty));
+    }
 
-    let name_filter = |entry: &DirEntry| {
-        config.names.is_empty()
-            || config
-                .names
-                .iter()
-                .any(|re| re.is_match(&entry.file_name().to_string_lossy()))
+    // Tests that read the file go last so that cheaper ones can rule it out.
+    for id in ["text_only", "binary_only"] {
+        if matches.get_flag(id) {
+            let flag = format!("--{}", id.replace('_', "-"));
+            exprs.push(Expr::Content(ContentFilter::parse(&flag, "")?));
+        }
+    }
+    let magic = matches
+        .get_many::<String>("magic")
+        .into_iter()
+        .flatten()
+        .map(|val| ContentFilter::parse("--magic", val).map(Expr::Content))
+        .collect::<MyResult<Vec<_>>>()?;
+    if !magic.is_empty() {
+        exprs.push(Expr::any(magic));
+    }
+    for val in matches.get_many::<String>("contains").into_iter().flatten() {
+        exprs.push(Expr::Content(ContentFilter::parse("--contains", val)?));
+    }
+    Ok(exprs)
+}
+
+/// Returns false if part of the tree couldn't be read or an action failed.
+pub fn run(config: Config) -> MyResult<bool> {
+    // Deleting a directory only works once everything in it is gone.
+    let contents_first = config
+        .expr
+        .contains(&|e| matches!(e, Expr::Action(Action::Delete { .. })));
+    // Commands run one at a time and in the order entries are found, as in
+    // find, so the walk can't be shared between threads.
+    let threads = if contents_first
+        || config
+            .expr
+            .contains(&|e| matches!(e, Expr::Action(Action::Exec(_))))
+    {
+        1
+    } else {
+        config.threads
     };
+    let mut out = BufWriter::new(io::stdout());
+    let mut clean = true;
 
-    for path in config.paths {
-        let entries = WalkDir::new(path)
-            .into_iter()
-            .filter_map(|e| match e {
-                Err(e) => {
-                    eprintln!("{}", e);
-                    None
+    if let Some(options) = &config.duplicates {
+        let candidates = Candidates::default();
+        for path in &config.paths {
+            clean &= walk::walk(path, &config, threads, contents_first, |entry, out| {
+                if config.expr.eval(entry, out)? {
+                    candidates.add(entry);
                 }
-                Ok(entry) => Some(entry),
-            })
-            .filter(type_filter)
-            .filter(name_filter)
-            .map(|entry| entry.path().display().to_string())
-            .collect::<Vec<_>>();
+                Ok(())
+            })?;
+        }
+        candidates.report(options, &mut out)?;
+        out.flush()?;
+        return Ok(clean);
+    }
 
-        println!("{}", entries.join("\n"));
+    for path in &config.paths {
+        if let Some(key) = config.sort {
+            // Sorting needs every entry up front; they are evaluated after.
+            let entries = Mutex::new(vec![]);
+            clean &= walk::walk(path, &config, threads, contents_first, |entry, _| {
+                entries.lock().unwrap().push(entry.clone());
+                Ok(())
+            })?;
+            let mut entries = entries.into_inner().unwrap();
+            key.sort(&mut entries, config.reverse, contents_first);
+            for entry in &entries {
+                config.expr.eval(entry, &mut out)?;
+            }
+            out.flush()?;
+        } else {
+            clean &= walk::walk(path, &config, threads, contents_first, |entry, out| {
+                config.expr.eval(entry, out).map(drop)
+            })?;
+        }
     }
-    Ok(())
+
+    // Whatever failed has been reported along the way
+    let finished = config.expr.finish(&mut out)?;
+    out.flush()?;
+    Ok(clean && finished)
 }
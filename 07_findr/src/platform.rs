@@ -0,0 +1,184 @@
+// Owners, modes, inodes and special files are unix things. Elsewhere
+// nobody owns anything, so -user and -group never match, and the mode
+// only tells whether the file is read-only.
+
+#[cfg(unix)]
+pub use self::unix::*;
+
+#[cfg(not(unix))]
+pub use self::other::*;
+
+#[cfg(unix)]
+mod unix {
+    use std::{
+        borrow::Cow,
+        ffi::OsStr,
+        fs::{FileType, Metadata},
+        os::unix::{
+            ffi::OsStrExt,
+            fs::{FileTypeExt, MetadataExt},
+        },
+        path::Path,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use crate::EntryType;
+    use users::{get_group_by_gid, get_group_by_name, get_user_by_name, get_user_by_uid};
+
+    /// Hard links to one file share its device and inode number.
+    pub type FileId = (u64, u64);
+
+    /// A path as written to the output.
+    pub fn bytes(s: &OsStr) -> Cow<'_, [u8]> {
+        Cow::Borrowed(s.as_bytes())
+    }
+
+    /// The permission bits, without the file type.
+    pub fn mode(metadata: &Metadata) -> u32 {
+        metadata.mode() & 0o7777
+    }
+
+    pub fn uid(metadata: &Metadata) -> Option<u32> {
+        Some(metadata.uid())
+    }
+
+    pub fn gid(metadata: &Metadata) -> Option<u32> {
+        Some(metadata.gid())
+    }
+
+    pub fn uid_by_name(name: &str) -> Option<u32> {
+        get_user_by_name(name).map(|user| user.uid())
+    }
+
+    pub fn gid_by_name(name: &str) -> Option<u32> {
+        get_group_by_name(name).map(|group| group.gid())
+    }
+
+    pub fn user_name(uid: u32) -> Option<String> {
+        get_user_by_uid(uid).map(|user| user.name().to_string_lossy().into_owned())
+    }
+
+    pub fn group_name(gid: u32) -> Option<String> {
+        get_group_by_gid(gid).map(|group| group.name().to_string_lossy().into_owned())
+    }
+
+    pub fn nlink(metadata: &Metadata) -> u64 {
+        metadata.nlink()
+    }
+
+    pub fn ino(metadata: &Metadata) -> Option<u64> {
+        Some(metadata.ino())
+    }
+
+    /// The 512-byte blocks the file takes up.
+    pub fn blocks(metadata: &Metadata) -> u64 {
+        metadata.blocks()
+    }
+
+    /// The last status change.
+    pub fn ctime(metadata: &Metadata) -> Option<SystemTime> {
+        let secs = u64::try_from(metadata.ctime()).ok()?;
+        let nanos = u32::try_from(metadata.ctime_nsec()).ok()?;
+        UNIX_EPOCH.checked_add(Duration::new(secs, nanos))
+    }
+
+    pub fn file_id(metadata: &Metadata, _path: &Path) -> FileId {
+        (metadata.dev(), metadata.ino())
+    }
+
+    /// Devices, FIFOs and sockets; anything else is `None`.
+    pub fn special_type(file_type: FileType) -> Option<EntryType> {
+        if file_type.is_block_device() {
+            Some(EntryType::Block)
+        } else if file_type.is_char_device() {
+            Some(EntryType::Char)
+        } else if file_type.is_fifo() {
+            Some(EntryType::Fifo)
+        } else if file_type.is_socket() {
+            Some(EntryType::Socket)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod other {
+    use std::{
+        borrow::Cow,
+        ffi::OsStr,
+        fs::{FileType, Metadata},
+        path::{Path, PathBuf},
+        time::SystemTime,
+    };
+
+    use crate::EntryType;
+
+    /// Without inode numbers, every path is a file of its own.
+    pub type FileId = PathBuf;
+
+    /// A path as written to the output, in UTF-8.
+    pub fn bytes(s: &OsStr) -> Cow<'_, [u8]> {
+        match s.to_string_lossy() {
+            Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
+            Cow::Owned(s) => Cow::Owned(s.into_bytes()),
+        }
+    }
+
+    pub fn mode(metadata: &Metadata) -> u32 {
+        if metadata.permissions().readonly() {
+            0o444
+        } else {
+            0o666
+        }
+    }
+
+    pub fn uid(_metadata: &Metadata) -> Option<u32> {
+        None
+    }
+
+    pub fn gid(_metadata: &Metadata) -> Option<u32> {
+        None
+    }
+
+    pub fn uid_by_name(_name: &str) -> Option<u32> {
+        None
+    }
+
+    pub fn gid_by_name(_name: &str) -> Option<u32> {
+        None
+    }
+
+    pub fn user_name(_uid: u32) -> Option<String> {
+        None
+    }
+
+    pub fn group_name(_gid: u32) -> Option<String> {
+        None
+    }
+
+    pub fn nlink(_metadata: &Metadata) -> u64 {
+        1
+    }
+
+    pub fn ino(_metadata: &Metadata) -> Option<u64> {
+        None
+    }
+
+    pub fn blocks(metadata: &Metadata) -> u64 {
+        metadata.len().div_ceil(512)
+    }
+
+    /// There's no status change time, so the last modification stands in.
+    pub fn ctime(metadata: &Metadata) -> Option<SystemTime> {
+        metadata.modified().ok()
+    }
+
+    pub fn file_id(_metadata: &Metadata, path: &Path) -> FileId {
+        path.to_path_buf()
+    }
+
+    pub fn special_type(_file_type: FileType) -> Option<EntryType> {
+        None
+    }
+}
@@ -0,0 +1,395 @@
+use std::{
+    ffi::OsStr,
+    fs::{self, FileType, Metadata},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use ignore::{ParallelVisitor, ParallelVisitorBuilder, WalkBuilder, WalkState};
+use walkdir::WalkDir;
+
+use crate::{Config, MyResult};
+
+/// A worker hands its output to stdout once this much has piled up.
+const CHUNK_BYTES: usize = 8 * 1024;
+
+/// Walks one search path on `threads` threads, calling `visit` for every
+/// entry that isn't ignored or shallower than `--min-depth`.
+///
+/// More than one thread walks with ignore's work-stealing walker, which
+/// visits entries in no particular order. Directories that come after
+/// their contents need the order, so a depth-first walk for `-delete` must
+/// use a single thread.
+///
+/// Returns false if any entry couldn't be read.
+pub fn walk<F>(
+    root: &str,
+    config: &Config,
+    threads: usize,
+    contents_first: bool,
+    visit: F,
+) -> MyResult<bool>
+where
+    F: Fn(&Entry, &mut dyn Write) -> MyResult<()> + Sync,
+{
+    // The parallel walker reads a search path of "-" as stdin.
+    if threads > 1 && !contents_first && root != "-" {
+        walk_parallel(root, config, threads, &visit)
+    } else {
+        walk_serial(root, config, contents_first, &visit)
+    }
+}
+
+fn walk_serial<F>(root: &str, config: &Config, contents_first: bool, visit: &F) -> MyResult<bool>
+where
+    F: Fn(&Entry, &mut dyn Write) -> MyResult<()>,
+{
+    let mut walker = WalkDir::new(root)
+        .same_file_system(config.one_file_system)
+        .follow_links(config.follow_links)
+        .contents_first(contents_first);
+    if let Some(max_depth) = config.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+
+    let mut out = Output::default();
+    let mut failed = false;
+    let mut entries = walker.into_iter();
+    while let Some(entry) = entries.next() {
+        let entry = match entry {
+            Ok(entry) => Entry::from(entry),
+            Err(e) => {
+                let err = WalkError::from(e);
+                match err.dangling_link() {
+                    Some(entry) => entry,
+                    None => {
+                        err.report();
+                        failed = true;
+                        continue;
+                    }
+                }
+            }
+        };
+
+        if let Some(rules) = &config.ignore {
+            if rules.is_ignored(&entry, contents_first) {
+                if entry.file_type().is_dir() && !contents_first {
+                    entries.skip_current_dir();
+                }
+                continue;
+            }
+        }
+        if entry.depth() < config.min_depth {
+            continue;
+        }
+
+        visit(&entry, &mut out)?;
+        if out.buf.len() >= CHUNK_BYTES {
+            out.flush()?;
+        }
+    }
+    out.flush()?;
+    Ok(!failed)
+}
+
+fn walk_parallel<F>(root: &str, config: &Config, threads: usize, visit: &F) -> MyResult<bool>
+where
+    F: Fn(&Entry, &mut dyn Write) -> MyResult<()> + Sync,
+{
+    let failed = AtomicBool::new(false);
+    let error = Mutex::new(None);
+    WalkBuilder::new(root)
+        .standard_filters(false)
+        .follow_links(config.follow_links)
+        .same_file_system(config.one_file_system)
+        .max_depth(config.max_depth)
+        .threads(threads)
+        .build_parallel()
+        .visit(&mut Shared {
+            config,
+            visit,
+            failed: &failed,
+            error: &error,
+        });
+
+    match error.into_inner().unwrap() {
+        Some(e) => Err(e.into()),
+        None => Ok(!failed.into_inner()),
+    }
+}
+
+/// What the workers of a parallel walk have in common. The first error
+/// stops the walk and is kept to be returned once every worker is done.
+struct Shared<'a, F> {
+    config: &'a Config,
+    visit: &'a F,
+    failed: &'a AtomicBool,
+    error: &'a Mutex<Option<String>>,
+}
+
+impl<F> Shared<'_, F> {
+    fn fail(&self, err: impl ToString) {
+        self.error
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| err.to_string());
+    }
+}
+
+impl<'s, F> ParallelVisitorBuilder<'s> for Shared<'s, F>
+where
+    F: Fn(&Entry, &mut dyn Write) -> MyResult<()> + Sync,
+{
+    fn build(&mut self) -> Box<dyn ParallelVisitor + 's> {
+        Box::new(Worker {
+            shared: Shared { ..*self },
+            out: Output::default(),
+        })
+    }
+}
+
+struct Worker<'a, F> {
+    shared: Shared<'a, F>,
+    out: Output,
+}
+
+impl<F> ParallelVisitor for Worker<'_, F>
+where
+    F: Fn(&Entry, &mut dyn Write) -> MyResult<()> + Sync,
+{
+    fn visit(&mut self, entry: Result<ignore::DirEntry, ignore::Error>) -> WalkState {
+        let shared = &self.shared;
+        let entry = match entry {
+            Ok(entry) => Entry::from(entry),
+            Err(e) => {
+                let err = WalkError::from(e);
+                match err.dangling_link() {
+                    Some(entry) => entry,
+                    None => {
+                        err.report();
+                        shared.failed.store(true, Ordering::Relaxed);
+                        return WalkState::Continue;
+                    }
+                }
+            }
+        };
+
+        if let Some(rules) = &shared.config.ignore {
+            if rules.is_ignored(&entry, false) {
+                return WalkState::Skip;
+            }
+        }
+        if entry.depth() < shared.config.min_depth {
+            return WalkState::Continue;
+        }
+
+        let result = (shared.visit)(&entry, &mut self.out).and_then(|_| {
+            if self.out.buf.len() >= CHUNK_BYTES {
+                self.out.flush()?;
+            }
+            Ok(())
+        });
+        match result {
+            Ok(()) => WalkState::Continue,
+            Err(e) => {
+                shared.fail(e);
+                WalkState::Quit
+            }
+        }
+    }
+}
+
+impl<F> Drop for Worker<'_, F> {
+    fn drop(&mut self) {
+        if let Err(e) = self.out.flush() {
+            self.shared.fail(e);
+        }
+    }
+}
+
+/// A walked entry, from either walker or made up for a dangling link.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    path: PathBuf,
+    file_type: FileType,
+    depth: usize,
+    path_is_symlink: bool,
+}
+
+impl Entry {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The type of the link's target when the link was followed.
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn path_is_symlink(&self) -> bool {
+        self.path_is_symlink
+    }
+
+    /// A search path such as ".." has no name of its own; it's used whole.
+    pub fn file_name(&self) -> &OsStr {
+        self.path.file_name().unwrap_or(self.path.as_os_str())
+    }
+
+    /// Follows the link if the walk did.
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        if self.path_is_symlink && !self.file_type.is_symlink() {
+            fs::metadata(&self.path)
+        } else {
+            fs::symlink_metadata(&self.path)
+        }
+    }
+}
+
+impl From<walkdir::DirEntry> for Entry {
+    fn from(entry: walkdir::DirEntry) -> Self {
+        Self {
+            file_type: entry.file_type(),
+            depth: entry.depth(),
+            path_is_symlink: entry.path_is_symlink(),
+            path: entry.into_path(),
+        }
+    }
+}
+
+impl From<ignore::DirEntry> for Entry {
+    fn from(entry: ignore::DirEntry) -> Self {
+        // Both walkers follow a linked search path, but only walkdir says so.
+        let path_is_symlink = entry.path_is_symlink()
+            || entry.depth() == 0
+                && fs::symlink_metadata(entry.path()).is_ok_and(|m| m.file_type().is_symlink());
+        Self {
+            file_type: entry.file_type().expect("only stdin has no file type"),
+            depth: entry.depth(),
+            path_is_symlink,
+            path: entry.into_path(),
+        }
+    }
+}
+
+/// One worker's output. It reaches stdout in whole chunks, so lines from
+/// different workers never interleave.
+#[derive(Default)]
+struct Output {
+    buf: Vec<u8>,
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&self.buf)?;
+            stdout.flush()?;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+}
+
+/// An entry that couldn't be read, from either walker.
+struct WalkError {
+    path: Option<PathBuf>,
+    depth: usize,
+    loop_ancestor: Option<PathBuf>,
+    message: String,
+}
+
+impl WalkError {
+    /// Neither walker can follow a link that points nowhere, which both do
+    /// for search paths and with `--follow`, but find reports the link
+    /// itself.
+    fn dangling_link(&self) -> Option<Entry> {
+        let path = self.path.as_ref()?;
+        if self.loop_ancestor.is_some() || fs::metadata(path).is_ok() {
+            return None;
+        }
+        let file_type = fs::symlink_metadata(path).ok()?.file_type();
+        file_type.is_symlink().then(|| Entry {
+            path: path.clone(),
+            file_type,
+            depth: self.depth,
+            path_is_symlink: true,
+        })
+    }
+
+    /// Walk errors are never fatal; the offending entry is reported and
+    /// skipped. Symlink loops can only occur with `--follow`.
+    fn report(&self) {
+        match (&self.path, &self.loop_ancestor) {
+            (Some(path), Some(ancestor)) => eprintln!(
+                "File system loop detected; \"{}\" is part of the same file system loop as \"{}\"",
+                path.display(),
+                ancestor.display()
+            ),
+            _ => eprintln!("{}", self.message),
+        }
+    }
+}
+
+impl From<walkdir::Error> for WalkError {
+    fn from(err: walkdir::Error) -> Self {
+        Self {
+            path: err.path().map(Path::to_path_buf),
+            depth: err.depth(),
+            loop_ancestor: err.loop_ancestor().map(Path::to_path_buf),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<ignore::Error> for WalkError {
+    fn from(err: ignore::Error) -> Self {
+        let mut walk_err = Self {
+            path: None,
+            depth: 0,
+            loop_ancestor: None,
+            message: err.to_string(),
+        };
+        // The path and depth wrap the underlying error in either order.
+        let mut err = &err;
+        loop {
+            match err {
+                ignore::Error::WithPath { path, err: inner } => {
+                    walk_err.path.get_or_insert_with(|| path.clone());
+                    err = inner;
+                }
+                ignore::Error::WithDepth { depth, err: inner } => {
+                    walk_err.depth = *depth;
+                    err = inner;
+                }
+                ignore::Error::Loop { ancestor, child } => {
+                    walk_err.path = Some(child.clone());
+                    walk_err.loop_ancestor = Some(ancestor.clone());
+                    break;
+                }
+                ignore::Error::Io(io_err) => {
+                    // Worded as walkdir words it.
+                    if let Some(path) = &walk_err.path {
+                        walk_err.message =
+                            format!("IO error for operation on {}: {}", path.display(), io_err);
+                    }
+                    break;
+                }
+                _ => break,
+            }
+        }
+        walk_err
+    }
+}
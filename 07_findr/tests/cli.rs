@@ -1,7 +1,17 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
-use std::{borrow::Cow, fs, path::Path};
+use filetime::{set_file_mtime, FileTime};
+use std::{
+    borrow::Cow,
+    fs,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+use tempfile::TempDir;
+
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -28,9 +38,10 @@ fn skips_bad_dir() -> TestResult {
     let bad = gen_bad_file();
     let expected = format!("{}: .* [(]os error [23][)]", &bad);
     Command::cargo_bin(PRG)?
-        .arg(&bad)
+        .args([&bad, "tests/inputs/g.csv"])
         .assert()
-        .success()
+        .failure()
+        .stdout("tests/inputs/g.csv\n")
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
 }
@@ -39,10 +50,21 @@ fn skips_bad_dir() -> TestResult {
 #[test]
 fn dies_bad_name() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&["--name", "*.csv"])
+        .args(["--name", "[a-z"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --name \"[a-z\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_regex() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--regex", "*.csv"])
         .assert()
         .failure()
-        .stderr(predicate::str::contains("Invalid --name \"*.csv\""));
+        .stderr(predicate::str::contains("Invalid --regex \"*.csv\""));
     Ok(())
 }
 
@@ -51,13 +73,71 @@ fn dies_bad_name() -> TestResult {
 fn dies_bad_type() -> TestResult {
     let expected = "error: 'x' isn't a valid value for '--type <TYPE>...'";
     Command::cargo_bin(PRG)?
-        .args(&["--type", "x"])
+        .args(["--type", "x"])
         .assert()
         .failure()
         .stderr(predicate::str::contains(expected));
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn dies_bad_size() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--size", "1q"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --size \"1q\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_mtime() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--mtime", "1d"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --mtime \"1d\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_perm() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--perm", "u+x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --perm \"u+x\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_user() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["--user", &bad])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!("Invalid --user \"{}\"", bad)));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_newer() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = format!("{}: .* [(]os error 2[)]", &bad);
+    Command::cargo_bin(PRG)?
+        .args(["--newer", &bad])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(expected)?);
+    Ok(())
+}
+
 // --------------------------------------------------
 #[cfg(windows)]
 fn format_file_name(expected_file: &str) -> Cow<str> {
@@ -67,7 +147,7 @@ fn format_file_name(expected_file: &str) -> Cow<str> {
 
 // --------------------------------------------------
 #[cfg(not(windows))]
-fn format_file_name(expected_file: &str) -> Cow<str> {
+fn format_file_name(expected_file: &str) -> Cow<'_, str> {
     // Equivalent to: Cow::Borrowed(expected_file)
     expected_file.into()
 }
@@ -228,7 +308,7 @@ fn type_f_l() -> TestResult {
 #[test]
 fn name_csv() -> TestResult {
     run(
-        &["tests/inputs", "-n", ".*[.]csv"],
+        &["tests/inputs", "-n", "*.csv"],
         "tests/expected/name_csv.txt",
     )
 }
@@ -237,7 +317,7 @@ fn name_csv() -> TestResult {
 #[test]
 fn name_csv_mp3() -> TestResult {
     run(
-        &["tests/inputs", "-n", ".*[.]csv", "-n", ".*[.]mp3"],
+        &["tests/inputs", "-n", "*.csv", "-n", "*.mp3"],
         "tests/expected/name_csv_mp3.txt",
     )
 }
@@ -246,7 +326,7 @@ fn name_csv_mp3() -> TestResult {
 #[test]
 fn name_txt_path_a_d() -> TestResult {
     run(
-        &["tests/inputs/a", "tests/inputs/d", "--name", ".*.txt"],
+        &["tests/inputs/a", "tests/inputs/d", "--name", "*.txt"],
         "tests/expected/name_txt_path_a_d.txt",
     )
 }
@@ -254,14 +334,14 @@ fn name_txt_path_a_d() -> TestResult {
 // --------------------------------------------------
 #[test]
 fn name_a() -> TestResult {
-    run(&["tests/inputs", "-n", "a"], "tests/expected/name_a.txt")
+    run(&["tests/inputs", "-n", "*a*"], "tests/expected/name_a.txt")
 }
 
 // --------------------------------------------------
 #[test]
 fn type_f_name_a() -> TestResult {
     run(
-        &["tests/inputs", "-t", "f", "-n", "a"],
+        &["tests/inputs", "-t", "f", "-n", "*a*"],
         "tests/expected/type_f_name_a.txt",
     )
 }
@@ -270,7 +350,7 @@ fn type_f_name_a() -> TestResult {
 #[test]
 fn type_d_name_a() -> TestResult {
     run(
-        &["tests/inputs", "--type", "d", "--name", "a"],
+        &["tests/inputs", "--type", "d", "--name", "*a*"],
         "tests/expected/type_d_name_a.txt",
     )
 }
@@ -295,17 +375,14 @@ fn unreadable_dir() -> TestResult {
     //permissions.set_mode(0o000);
 
     std::process::Command::new("chmod")
-        .args(&["000", dirname])
+        .args(["000", dirname])
         .status()
         .expect("failed");
 
-    let cmd = Command::cargo_bin(PRG)?
-        .arg("tests/inputs")
-        .assert()
-        .success();
+    let out = Command::cargo_bin(PRG)?.arg("tests/inputs").output()?;
     fs::remove_dir(dirname)?;
+    assert!(!out.status.success());
 
-    let out = cmd.get_output();
     let stdout = String::from_utf8(out.stdout.clone())?;
     let lines: Vec<&str> =
         stdout.split("\n").filter(|s| !s.is_empty()).collect();
@@ -316,3 +393,1034 @@ fn unreadable_dir() -> TestResult {
     assert!(stderr.contains("cant-touch-this: Permission denied"));
     Ok(())
 }
+
+// --------------------------------------------------
+#[test]
+fn size_2c() -> TestResult {
+    run(&["tests/inputs", "--size", "2c"], "tests/expected/type_f.txt")
+}
+
+// --------------------------------------------------
+#[test]
+fn size_range() -> TestResult {
+    run(
+        &["tests/inputs", "--size", "+1c", "--size", "-3c"],
+        "tests/expected/type_f.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn user_current() -> TestResult {
+    let uid = users::get_current_uid().to_string();
+    run(&["tests/inputs", "--user", &uid], "tests/expected/path1.txt")
+}
+
+// --------------------------------------------------
+fn run_tmp(dir: &TempDir, args: &[&str], expected: &[&str]) -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .arg(dir.path())
+        .args(args)
+        .assert()
+        .success();
+    let out = cmd.get_output();
+    let stdout = String::from_utf8(out.stdout.clone())?;
+    let root = dir.path().display().to_string();
+    let mut lines: Vec<&str> = stdout
+        .split("\n")
+        .filter_map(|s| s.strip_prefix(&root))
+        .collect();
+    lines.sort();
+
+    let mut expected = expected.to_vec();
+    expected.sort();
+    assert_eq!(lines, expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+fn mk_tree() -> Result<TempDir, Box<dyn std::error::Error>> {
+    let dir = TempDir::new()?;
+    fs::create_dir(dir.path().join("empty_dir"))?;
+    fs::create_dir(dir.path().join("full_dir"))?;
+    fs::write(dir.path().join("empty.txt"), "")?;
+    fs::write(dir.path().join("full_dir/big.txt"), vec![b'x'; 3000])?;
+
+    let script = dir.path().join("full_dir/run.sh");
+    fs::write(&script, "#!/bin/sh\n")?;
+    #[cfg(unix)]
+    {
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+        fs::set_permissions(
+            dir.path().join("empty.txt"),
+            fs::Permissions::from_mode(0o600),
+        )?;
+        fs::set_permissions(
+            dir.path().join("full_dir/big.txt"),
+            fs::Permissions::from_mode(0o644),
+        )?;
+    }
+
+    let old = SystemTime::now() - Duration::from_secs(3 * 24 * 60 * 60);
+    set_file_mtime(
+        dir.path().join("full_dir/big.txt"),
+        FileTime::from_system_time(old),
+    )?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+fn size_suffix() -> TestResult {
+    let dir = mk_tree()?;
+    run_tmp(&dir, &["-t", "f", "--size", "+2k"], &["/full_dir/big.txt"])?;
+    run_tmp(&dir, &["-t", "f", "--size", "3k"], &["/full_dir/big.txt"])?;
+    run_tmp(&dir, &["-t", "f", "--size", "-1k"], &["/empty.txt"])
+}
+
+// --------------------------------------------------
+#[test]
+fn empty() -> TestResult {
+    let dir = mk_tree()?;
+    run_tmp(&dir, &["--empty"], &["/empty.txt", "/empty_dir"])
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn perm() -> TestResult {
+    let dir = mk_tree()?;
+    run_tmp(&dir, &["-t", "f", "--perm", "600"], &["/empty.txt"])?;
+    run_tmp(&dir, &["-t", "f", "--perm", "-111"], &["/full_dir/run.sh"])?;
+    run_tmp(
+        &dir,
+        &["-t", "f", "--perm", "/044"],
+        &["/full_dir/big.txt", "/full_dir/run.sh"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn mtime() -> TestResult {
+    let dir = mk_tree()?;
+    run_tmp(&dir, &["-t", "f", "--mtime", "+1"], &["/full_dir/big.txt"])?;
+    run_tmp(&dir, &["-t", "f", "--mtime", "3"], &["/full_dir/big.txt"])?;
+    run_tmp(
+        &dir,
+        &["-t", "f", "--mmin", "-60"],
+        &["/empty.txt", "/full_dir/run.sh"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn newer() -> TestResult {
+    let dir = mk_tree()?;
+    let reference = dir.path().join("full_dir/big.txt");
+    run_tmp(
+        &dir,
+        &["-t", "f", "--newer", &reference.display().to_string()],
+        &["/empty.txt", "/full_dir/run.sh"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn max_depth_1() -> TestResult {
+    run(
+        &["tests/inputs", "--max-depth", "1"],
+        "tests/expected/max_depth_1.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn min_depth_3() -> TestResult {
+    run(
+        &["tests/inputs", "--mindepth", "3"],
+        "tests/expected/min_depth_3.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn max_depth_0() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a", "--maxdepth", "0"])
+        .assert()
+        .success()
+        .stdout("tests/inputs/a\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_type_f_path_d() -> TestResult {
+    run(
+        &["tests/inputs/d", "-L", "-t", "f"],
+        "tests/expected/follow_type_f_path_d.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_type_l() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--follow", "-t", "l"])
+        .assert()
+        .success();
+    assert!(cmd.get_output().stdout.iter().all(|b| *b == b'\n'));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn follow_loop() -> TestResult {
+    let dir = TempDir::new()?;
+    fs::create_dir_all(dir.path().join("a/b"))?;
+    std::os::unix::fs::symlink("../..", dir.path().join("a/b/up"))?;
+
+    let cmd = Command::cargo_bin(PRG)?
+        .arg(dir.path())
+        .arg("-L")
+        .assert()
+        .failure();
+    let out = cmd.get_output();
+    let stderr = String::from_utf8(out.stderr.clone())?;
+    assert!(stderr.contains("File system loop detected"));
+    assert!(stderr.contains("a/b/up"));
+
+    let stdout = String::from_utf8(out.stdout.clone())?;
+    assert_eq!(stdout.lines().count(), 3);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn expr_csv_tsv_not_d() -> TestResult {
+    run(
+        &[
+            "tests/inputs",
+            "(",
+            "-name",
+            "*.csv",
+            "-o",
+            "-name",
+            "*.tsv",
+            ")",
+            "-not",
+            "-path",
+            "*/d/*",
+        ],
+        "tests/expected/expr_csv_tsv_not_d.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn expr_type_f_not_name_a() -> TestResult {
+    run(
+        &["tests/inputs", "-t", "f", "!", "-name", "a.*"],
+        "tests/expected/expr_type_f_not_name_a.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn expr_type_d_or_mp3() -> TestResult {
+    run(
+        &["tests/inputs", "-type", "d", "-o", "-name", "*.mp3"],
+        "tests/expected/expr_type_d_or_mp3.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn expr_after_option_value() -> TestResult {
+    // An option's value is never the start of the expression
+    for args in [&["--name", "("][..], &["--contains", "!"], &["-n", "!", "-type", "d"]] {
+        Command::cargo_bin(PRG)?
+            .arg("tests/inputs/a")
+            .args(args)
+            .assert()
+            .success()
+            .stdout("");
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_expr() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "(", "-name", "a"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Missing \")\" in expression"));
+
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-type", "x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid -type \"x\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn print0() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-name", "*.csv", "-print0"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    assert!(stdout.ends_with('\0'));
+
+    let mut lines: Vec<&str> = stdout.split('\0').filter(|s| !s.is_empty()).collect();
+    lines.sort();
+    assert_eq!(
+        lines,
+        ["tests/inputs/a/b/b.csv", "tests/inputs/d/b.csv", "tests/inputs/g.csv"]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn printf() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a/b", "-name", "*.csv", "-printf", "%y %s %d %f|%P|%h\\n"])
+        .assert()
+        .success()
+        .stdout("f 2 1 b.csv|b.csv|tests/inputs/a/b\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/d/b.csv", "-printf", "[%-6f] %l %%\\0"])
+        .assert()
+        .success()
+        .stdout("[b.csv ] ../a/b/b.csv %\0");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_printf() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-printf", "%q"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid -printf directive \"%q\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_each() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a/b", "-name", "*.csv", "-exec", "echo", "x{}x", ";"])
+        .assert()
+        .success()
+        .stdout("xtests/inputs/a/b/b.csvx\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_each_result() -> TestResult {
+    // -exec is true when the command succeeds, so it can act as a test
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/d", "-exec", "test", "-L", "{}", ";", "-print"])
+        .assert()
+        .success()
+        .stdout("tests/inputs/d/b.csv\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_each_in_turn() -> TestResult {
+    // Each command finishes before the next starts, even with threads
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-j", "4", "-type", "f", "-exec"])
+        .args(["sh", "-c", "echo start; sleep 0.01; echo end", ";"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines.len() > 2);
+    for pair in lines.chunks(2) {
+        assert_eq!(pair, ["start", "end"]);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_batch() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["tests/inputs/d", "-type", "f", "-exec", "echo", "files:", "{}", "+"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    assert_eq!(stdout.lines().count(), 1);
+
+    let mut words: Vec<&str> = stdout.split_whitespace().collect();
+    assert_eq!(words.remove(0), "files:");
+    words.sort();
+    assert_eq!(
+        words,
+        [
+            "tests/inputs/d/d.tsv",
+            "tests/inputs/d/d.txt",
+            "tests/inputs/d/e/e.mp3"
+        ]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_batch_fails() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/d", "-exec", "false", "{}", "+"])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_not_found() -> TestResult {
+    // A command that can't be started is false and fails the run
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a/b", "-name", "*.csv", "-exec", "no-such-command", "{}", ";"])
+        .args(["-o", "-name", "*.csv", "-print"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::starts_with("no-such-command: "))
+        .stdout("tests/inputs/a/b/b.csv\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn execdir() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a/b", "-name", "*.csv", "-execdir", "pwd", ";"])
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with("tests/inputs/a/b\n"));
+
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a", "-type", "f", "-execdir", "echo", "{}", "+"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("./a.txt\n"))
+        .stdout(predicate::str::contains("./b.csv\n"))
+        .stdout(predicate::str::contains("./c.mp3\n"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn delete() -> TestResult {
+    let dir = mk_tree()?;
+    Command::cargo_bin(PRG)?
+        .arg(dir.path())
+        .args(["-path", "*/full_dir*", "-delete"])
+        .assert()
+        .success()
+        .stdout("");
+
+    assert!(!dir.path().join("full_dir").exists());
+    assert!(dir.path().join("empty_dir").exists());
+    assert!(dir.path().join("empty.txt").exists());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn delete_fails() -> TestResult {
+    // full_dir still has files in it, so it can't be removed
+    let dir = mk_tree()?;
+    Command::cargo_bin(PRG)?
+        .arg(dir.path())
+        .args(["-name", "full_dir", "-delete"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot delete"))
+        .stdout("");
+    assert!(dir.path().join("full_dir/big.txt").exists());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn name_exact() -> TestResult {
+    // A glob without wildcards matches the whole name only
+    run(&["tests/inputs", "-n", "a"], "tests/expected/name_exact_a.txt")
+}
+
+// --------------------------------------------------
+#[test]
+fn name_glob_class() -> TestResult {
+    run(
+        &["tests/inputs", "-name", "[!a-c].*"],
+        "tests/expected/name_glob_class.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn iname() -> TestResult {
+    run(
+        &["tests/inputs", "--iname", "*.CSV"],
+        "tests/expected/name_csv.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn path_glob() -> TestResult {
+    run(
+        &["tests/inputs", "--path", "*/b/*"],
+        "tests/expected/path_glob_b.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn regex_whole_path() -> TestResult {
+    run(
+        &["tests/inputs", "--regex", ".*[.]csv"],
+        "tests/expected/name_csv.txt",
+    )?;
+    run(
+        &["tests/inputs", "-iregex", "TESTS/.*/[A-C][.].*"],
+        "tests/expected/regex_a_c.txt",
+    )
+}
+
+// --------------------------------------------------
+fn mk_ignore_tree() -> Result<TempDir, Box<dyn std::error::Error>> {
+    let dir = TempDir::new()?;
+    for sub in [".git", "build", "src/build", "src/target", "src/gen"] {
+        fs::create_dir_all(dir.path().join(sub))?;
+    }
+    for file in [
+        ".git/HEAD",
+        ".hidden",
+        "a.log",
+        "keep.log",
+        "build/out.o",
+        "src/main.rs",
+        "src/build/mod.rs",
+        "src/target/x.o",
+        "src/gen/gen.rs",
+        "src/gen/extra.rs",
+    ] {
+        fs::write(dir.path().join(file), "")?;
+    }
+    fs::write(dir.path().join(".gitignore"), "*.log\n!keep.log\n/build\ntarget/\n")?;
+    fs::write(dir.path().join("src/gen/.gitignore"), "*.rs\n")?;
+    fs::write(dir.path().join("src/gen/.ignore"), "!gen.rs\n")?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+fn ignore() -> TestResult {
+    let dir = mk_ignore_tree()?;
+    run_tmp(
+        &dir,
+        &["--ignore"],
+        &[
+            "",
+            "/keep.log",
+            "/src",
+            "/src/build",
+            "/src/build/mod.rs",
+            "/src/gen",
+            "/src/gen/gen.rs",
+            "/src/main.rs",
+        ],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn ignore_hidden() -> TestResult {
+    let dir = mk_ignore_tree()?;
+    run_tmp(
+        &dir,
+        &["--ignore", "--hidden", "-type", "f", "-name", ".*"],
+        &["/.gitignore", "/.hidden", "/src/gen/.gitignore", "/src/gen/.ignore"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn ignore_min_depth() -> TestResult {
+    let dir = mk_ignore_tree()?;
+    run_tmp(
+        &dir,
+        &["--ignore", "--min-depth", "2"],
+        &[
+            "/src/build",
+            "/src/build/mod.rs",
+            "/src/gen",
+            "/src/gen/gen.rs",
+            "/src/main.rs",
+        ],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn ignore_delete() -> TestResult {
+    let dir = mk_ignore_tree()?;
+    run_tmp(&dir, &["--ignore", "-name", "*.o", "-delete"], &[])?;
+    assert!(dir.path().join("build/out.o").exists());
+    assert!(dir.path().join("src/target/x.o").exists());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn ignore_subdirectory() -> TestResult {
+    // Rules from above the search path apply, up to the repository root
+    let dir = mk_ignore_tree()?;
+    fs::create_dir_all(dir.path().join(".git/info"))?;
+    fs::write(dir.path().join(".git/info/exclude"), "secret\n")?;
+    fs::create_dir_all(dir.path().join("src/secret"))?;
+    fs::write(dir.path().join("src/a.log"), "")?;
+    // Only the repository's own exclude file counts
+    fs::create_dir_all(dir.path().join("src/gen/.git/info"))?;
+    fs::write(dir.path().join("src/gen/.git/info/exclude"), "gen.rs\n")?;
+
+    let src = dir.path().join("src");
+    let cmd = Command::cargo_bin(PRG)?
+        .arg(&src)
+        .args(["--ignore", "-type", "f"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let root = src.display().to_string();
+    let mut lines: Vec<&str> = stdout
+        .lines()
+        .filter_map(|s| s.strip_prefix(&root))
+        .collect();
+    lines.sort();
+    assert_eq!(lines, ["/build/mod.rs", "/gen/gen.rs", "/main.rs"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn hidden_requires_ignore() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg("--hidden")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--ignore"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn threads() -> TestResult {
+    run(&["tests/inputs", "--threads", "4"], "tests/expected/path1.txt")?;
+
+    let dir = TempDir::new()?;
+    let mut expected = vec![String::new()];
+    for i in 0..10 {
+        for j in 0..5 {
+            let sub = format!("/d{}/e{}", i, j);
+            fs::create_dir_all(dir.path().join(&sub[1..]))?;
+            fs::write(dir.path().join(format!("{}/f.txt", &sub[1..])), "")?;
+            expected.push(sub.clone());
+            expected.push(format!("{}/f.txt", sub));
+        }
+        expected.push(format!("/d{}", i));
+    }
+    let expected: Vec<&str> = expected.iter().map(String::as_str).collect();
+    run_tmp(&dir, &["-j", "4"], &expected)
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_threads() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--threads", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value '0'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sort_path() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a", "tests/inputs/d", "--sort", "path", "-j", "4"])
+        .assert()
+        .success()
+        .stdout(
+            "tests/inputs/a\n\
+             tests/inputs/a/a.txt\n\
+             tests/inputs/a/b\n\
+             tests/inputs/a/b/b.csv\n\
+             tests/inputs/a/b/c\n\
+             tests/inputs/a/b/c/c.mp3\n\
+             tests/inputs/d\n\
+             tests/inputs/d/b.csv\n\
+             tests/inputs/d/d.tsv\n\
+             tests/inputs/d/d.txt\n\
+             tests/inputs/d/e\n\
+             tests/inputs/d/e/e.mp3\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_sorted(args: &[&str], expected: &str) -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-j", "4"])
+        .args(args)
+        .args(["-type", "f", "-printf", "%P\n"])
+        .assert()
+        .success()
+        .stdout(expected.to_string());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sort_path_reverse() -> TestResult {
+    run_sorted(
+        &["--sort", "path", "--reverse"],
+        "g.csv\nf/f.txt\nd/e/e.mp3\nd/d.txt\nd/d.tsv\na/b/c/c.mp3\n\
+         a/b/b.csv\na/a.txt\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn sort_name() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--sort", "name", "--min-depth", "1"])
+        .args(["-printf", "%P\n"])
+        .assert()
+        .success()
+        .stdout(
+            "a\na/a.txt\na/b\na/b/b.csv\nd/b.csv\na/b/c\na/b/c/c.mp3\nd\n\
+             d/d.tsv\nd/d.txt\nd/e\nd/e/e.mp3\nf\nf/f.txt\ng.csv\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sort_size_mtime() -> TestResult {
+    let dir = TempDir::new()?;
+    let now = SystemTime::now();
+    for (name, size, age) in [("a", 30, 1), ("b", 10, 3), ("c", 20, 2), ("d", 10, 0)] {
+        let path = dir.path().join(name);
+        fs::write(&path, vec![b'x'; size])?;
+        let mtime = now - Duration::from_secs(age * 60 * 60);
+        set_file_mtime(&path, FileTime::from_system_time(mtime))?;
+    }
+
+    for (args, expected) in [
+        (&["--sort", "size"][..], "b\nd\nc\na\n"),
+        (&["--sort", "size", "-r"], "a\nc\nd\nb\n"),
+        (&["--sort", "mtime"], "b\nc\na\nd\n"),
+        (&["--sort", "mtime", "-r"], "d\na\nc\nb\n"),
+    ] {
+        Command::cargo_bin(PRG)?
+            .arg(dir.path())
+            .args(args)
+            .args(["-type", "f", "-printf", "%f\n"])
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sort_delete() -> TestResult {
+    let dir = mk_tree()?;
+    Command::cargo_bin(PRG)?
+        .arg(dir.path())
+        .args(["--sort", "path", "-path", "*/full_dir*", "-delete"])
+        .assert()
+        .success()
+        .stdout("");
+    assert!(!dir.path().join("full_dir").exists());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sort_name_delete() -> TestResult {
+    // full_dir sorts before run.sh by name, but is still deleted after it
+    let dir = mk_tree()?;
+    Command::cargo_bin(PRG)?
+        .arg(dir.path())
+        .args(["--sort", "name", "-path", "*/full_dir*", "-delete"])
+        .assert()
+        .success()
+        .stdout("");
+    assert!(!dir.path().join("full_dir").exists());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn no_matches() -> TestResult {
+    for args in [&["tests/inputs", "-name", "nope"][..], &["tests/inputs", "--sort", "path", "-false"]] {
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .assert()
+            .success()
+            .stdout("");
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_sort() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--sort", "color"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'color'"));
+    Command::cargo_bin(PRG)?
+        .arg("--reverse")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--sort <KEY>"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn special_types() -> TestResult {
+    let dir = TempDir::new()?;
+    std::process::Command::new("mkfifo")
+        .arg(dir.path().join("fifo"))
+        .status()?;
+    let _socket = std::os::unix::net::UnixListener::bind(dir.path().join("socket"))?;
+    fs::write(dir.path().join("file"), "")?;
+
+    run_tmp(&dir, &["--type", "p"], &["/fifo"])?;
+    run_tmp(&dir, &["-type", "s"], &["/socket"])?;
+    run_tmp(&dir, &["-type", "p,s", "-o", "-type", "f"], &["/fifo", "/file", "/socket"])?;
+    run_tmp(&dir, &["-type", "f", "-printf", "%p/%y\n"], &["/file/f"])?;
+
+    Command::cargo_bin(PRG)?
+        .args(["/dev/null", "-type", "c"])
+        .assert()
+        .success()
+        .stdout("/dev/null\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn xtype() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/d", "-xtype", "f", "-name", "b.csv"])
+        .assert()
+        .success()
+        .stdout("tests/inputs/d/b.csv\n");
+
+    let dir = TempDir::new()?;
+    std::os::unix::fs::symlink("missing", dir.path().join("broken"))?;
+    std::os::unix::fs::symlink(".", dir.path().join("here"))?;
+    run_tmp(&dir, &["--xtype", "l"], &["/broken"])?;
+    run_tmp(&dir, &["-xtype", "d"], &["", "/here"])?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn follow_broken_link() -> TestResult {
+    // A link that can't be followed is still found, as a link
+    let dir = TempDir::new()?;
+    fs::create_dir(dir.path().join("sub"))?;
+    std::os::unix::fs::symlink("missing", dir.path().join("sub/broken"))?;
+    for args in [&["--follow", "-type", "l"][..], &["-type", "l"], &["-xtype", "l"]] {
+        Command::cargo_bin(PRG)?
+            .arg(dir.path())
+            .args(args)
+            .args(["-printf", "%d %y %f\n"])
+            .assert()
+            .success()
+            .stderr("")
+            .stdout("2 l broken\n");
+    }
+    Command::cargo_bin(PRG)?
+        .arg(dir.path().join("sub/broken"))
+        .args(["-printf", "%d %y %f\n"])
+        .assert()
+        .success()
+        .stdout("0 l broken\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn contains() -> TestResult {
+    run(
+        &["tests/content", "--contains", "ne+dle"],
+        "tests/expected/contains_needle.txt",
+    )?;
+    run(
+        &["tests/content", "--contains", "^hay$"],
+        "tests/expected/contains_hay.txt",
+    )?;
+    run(
+        &["tests/content", "-type", "f", "-not", "-contains", "needle"],
+        "tests/expected/not_contains_needle.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn contains_long_line() -> TestResult {
+    // A line is read in pieces, which overlap where a match might be cut.
+    // The file is made here as it's too big to check in.
+    let dir = TempDir::new()?;
+    let mut line = vec![b'x'; 64 * 1024 - 3];
+    line.extend_from_slice(b"needle");
+    line.extend(vec![b'x'; 200 * 1024]);
+    fs::write(dir.path().join("long.txt"), line)?;
+    run_tmp(&dir, &["--contains", "needle"], &["/long.txt"])?;
+    run_tmp(&dir, &["--contains", "needles"], &[])
+}
+
+// --------------------------------------------------
+#[test]
+fn text_binary() -> TestResult {
+    run(&["tests/content", "--text-only"], "tests/expected/text_only.txt")?;
+    run(&["tests/content", "--binary-only"], "tests/expected/binary_only.txt")?;
+    run(
+        &["tests/content", "-binary", "-o", "-name", "run.sh"],
+        "tests/expected/binary_or_run.txt",
+    )?;
+    Command::cargo_bin(PRG)?
+        .args(["tests/content", "--binary-only", "--contains", "needle"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn magic() -> TestResult {
+    run(&["tests/content", "--magic", "png"], "tests/expected/magic_png.txt")?;
+    run(
+        &["tests/content", "--magic", "png", "--magic", "script"],
+        "tests/expected/magic_png_script.txt",
+    )?;
+    Command::cargo_bin(PRG)?
+        .args(["tests/content", "-magic", "gzip"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_content() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--contains", "(x"])
+        .assert()
+        .failure()
+        .stderr("Invalid --contains \"(x\"\n");
+    Command::cargo_bin(PRG)?
+        .args([".", "-magic", "docx"])
+        .assert()
+        .failure()
+        .stderr("Invalid -magic \"docx\"\n");
+    Command::cargo_bin(PRG)?
+        .args(["--text-only", "--binary-only"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn duplicates() -> TestResult {
+    let expected = fs::read_to_string(format_file_name("tests/expected/duplicates.txt").as_ref())?;
+    Command::cargo_bin(PRG)?
+        .args(["tests/dupes", "--duplicates", "-j", "4"])
+        .assert()
+        .success()
+        .stdout(expected);
+
+    let summary = format_file_name("tests/expected/duplicates_summary.txt");
+    Command::cargo_bin(PRG)?
+        .args(["tests/dupes", "--duplicates", "--summary", "-name", "*.txt"])
+        .assert()
+        .success()
+        .stdout(fs::read_to_string(summary.as_ref())?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn duplicates_hardlinks() -> TestResult {
+    // Git can't check in a hard link, so the tree is made here
+    let dir = TempDir::new()?;
+    fs::write(dir.path().join("a.txt"), "hello")?;
+    fs::hard_link(dir.path().join("a.txt"), dir.path().join("link.txt"))?;
+    let root = dir.path().display();
+    Command::cargo_bin(PRG)?
+        .arg(dir.path())
+        .args(["--duplicates", "--hardlinks", "--summary"])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{root}/a.txt\n{root}/link.txt\n\n1 duplicate files in 1 sets, 0 bytes wasted\n"
+        ));
+    Command::cargo_bin(PRG)?
+        .arg(dir.path())
+        .args(["--duplicates", "--summary"])
+        .assert()
+        .success()
+        .stdout("0 duplicate files in 0 sets, 0 bytes wasted\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["tests/dupes", "--duplicates", "--summary", "-name", "c.txt"])
+        .assert()
+        .success()
+        .stdout("0 duplicate files in 0 sets, 0 bytes wasted\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_duplicates_actions() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--duplicates", ".", "-print"])
+        .assert()
+        .failure()
+        .stderr("--duplicates can't be used with actions\n");
+    Ok(())
+}
@@ -5,6 +5,9 @@ set -u
 CSV="tests/inputs/movies1.csv"
 TSV="tests/inputs/movies1.tsv"
 BOOKS="tests/inputs/books.tsv"
+QUOTED="tests/inputs/quoted.csv"
+MIXED="tests/inputs/mixed.tsv"
+LATIN1="tests/inputs/latin1.txt"
 OUT_DIR="tests/expected"
 
 [[ ! -d "$OUT_DIR" ]] && mkdir -p "$OUT_DIR"
@@ -13,6 +16,7 @@ rm -f $OUT_DIR/*
 for FLD in 1 2 3 1-2 2-3 1-3; do
     cut -f $FLD      $TSV > "$OUT_DIR/$(basename $TSV).f${FLD}.out"
     cut -f $FLD -d , $CSV > "$OUT_DIR/$(basename $CSV).f${FLD}.dcomma.out"
+    cut -f $FLD -d , $QUOTED > "$OUT_DIR/$(basename $QUOTED).f${FLD}.dcomma.out"
 done
 
 for POS in 1 2 8 1-2 2-3 1-8; do
@@ -25,4 +29,34 @@ for POS in 1 2 8 1-2 2-3 1-8; do
     cut -c $POS $CSV > "$OUT_DIR/$(basename $CSV).c${POS}.out"
 done
 
+for FLD in 2- -2 2-2; do
+    cut -f $FLD $TSV > "$OUT_DIR/$(basename $TSV).f${FLD}.out"
+    cut -c $FLD $CSV > "$OUT_DIR/$(basename $CSV).c${FLD}.out"
+    cut -b $FLD $CSV > "$OUT_DIR/$(basename $CSV).b${FLD}.out"
+done
+
+for FLD in 2 1,3 2-; do
+    cut --complement -f $FLD $TSV > "$OUT_DIR/$(basename $TSV).f${FLD}.complement.out"
+    cut --complement -c $FLD $CSV > "$OUT_DIR/$(basename $CSV).c${FLD}.complement.out"
+done
+
+for FLD in 3,1 2,1-2 3,2-; do
+    cut -f $FLD $TSV > "$OUT_DIR/$(basename $TSV).f${FLD}.gnu.out"
+    cut -c $FLD $CSV > "$OUT_DIR/$(basename $CSV).c${FLD}.gnu.out"
+done
+
+for FLD in 1 2 1,3; do
+    cut -f $FLD $MIXED > "$OUT_DIR/$(basename $MIXED).f${FLD}.out"
+    cut -s -f $FLD $MIXED > "$OUT_DIR/$(basename $MIXED).f${FLD}.only-delimited.out"
+done
+
+cut -f 1,3 --output-delimiter " | " $TSV > "$OUT_DIR/$(basename $TSV).f1,3.odelim.out"
+cut -f 1- -d , --output-delimiter $'\t' $CSV > "$OUT_DIR/$(basename $CSV).f1-.odelim.out"
+cut -c 1-2,4- --output-delimiter : $CSV > "$OUT_DIR/$(basename $CSV).c1-2,4-.odelim.out"
+cut -b 1,3,5 --output-delimiter :: $CSV > "$OUT_DIR/$(basename $CSV).b1,3,5.odelim.out"
+
+for POS in 5 1-8 5-; do
+    cut -b $POS $LATIN1 > "$OUT_DIR/$(basename $LATIN1).b${POS}.out"
+done
+
 echo -e "AA\nÉÉ\nSS\nJJ" > "$OUT_DIR/books.c1,1.out"
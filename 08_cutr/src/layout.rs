@@ -0,0 +1,114 @@
+use std::{fs, ops::Range};
+
+use crate::{parse_index, MyResult, PositionList};
+
+/// Named fixed-width columns, read from a `--layout` file. Each line has a
+/// column's name, its first character (counting from 1) and its width,
+/// separated by whitespace. Blank lines and lines starting with `#` are
+/// skipped.
+#[derive(Debug)]
+pub struct Layout {
+    names: Vec<String>,
+    columns: PositionList,
+}
+
+impl Layout {
+    pub fn from_file(path: &str) -> MyResult<Self> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&contents).map_err(|e| From::from(format!("{}: {}", path, e)))
+    }
+
+    fn parse(contents: &str) -> MyResult<Self> {
+        let mut layout = Self {
+            names: vec![],
+            columns: vec![],
+        };
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let column = match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [name, start, width] => parse_index(start)
+                    .and_then(|start| Ok(start..start + parse_index(width)? + 1))
+                    .map(|range| (name, range)),
+                _ => Err("expected NAME START WIDTH".to_string()),
+            };
+            let (name, range) = column.map_err(|e| format!("line {}: {}", i + 1, e))?;
+            layout.names.push(name.to_string());
+            layout.columns.push(range);
+        }
+        if layout.columns.is_empty() {
+            return Err(From::from("no columns"));
+        }
+        Ok(layout)
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// The character ranges of the columns at the given indices, in order.
+    pub fn columns(&self, pos: &[Range<usize>]) -> PositionList {
+        crate::resolve(pos, self.columns.len())
+            .flat_map(|range| self.columns[range].iter().cloned())
+            .collect()
+    }
+}
+
+/// The characters of each column, with `separator` between columns. A
+/// column past the end of a short line is empty, so every line has the same
+/// number of columns. With `trim`, the spaces padding each one are removed.
+pub fn extract_layout(line: &str, columns: &[Range<usize>], separator: &str, trim: bool) -> String {
+    let chars: Vec<_> = line.chars().collect();
+    columns
+        .iter()
+        .map(|column| {
+            let text: String = chars[column.start.min(chars.len())..column.end.min(chars.len())]
+                .iter()
+                .collect();
+            if trim {
+                text.trim_matches(' ').to_string()
+            } else {
+                text
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+    use super::{extract_layout, Layout};
+
+    #[test]
+    fn test_parse() {
+        let layout = Layout::parse("# name start width\nid 1 4\n\nname  5 10\n").unwrap();
+        assert_eq!(layout.names(), ["id", "name"]);
+        assert_eq!(layout.columns, [0..4, 4..14]);
+        assert_eq!(layout.columns(&[1..2, 0..1]), [4..14, 0..4]);
+        assert_eq!(layout.columns(&[1..usize::MAX]), [4..14]);
+
+        let err = |contents| Layout::parse(contents).unwrap_err().to_string();
+        assert_eq!(err("id 1\n"), "line 1: expected NAME START WIDTH");
+        assert_eq!(err("id 1 4\nname 0 2"), "line 2: illegal list value: \"0\"");
+        assert_eq!(err("id 1 x"), "line 1: illegal list value: \"x\"");
+        assert_eq!(err("# nothing\n"), "no columns");
+    }
+
+    #[test]
+    fn test_extract_layout() {
+        let columns = [0..4, 4..10, 10..12];
+        assert_eq!(
+            extract_layout("0001Émile 42", &columns, "\t", false),
+            "0001\tÉmile \t42"
+        );
+        assert_eq!(
+            extract_layout("0001Émile 42", &columns, ",", true),
+            "0001,Émile,42"
+        );
+        assert_eq!(extract_layout("0002Bo", &columns, ",", false), "0002,Bo,");
+        assert_eq!(extract_layout("", &columns, ",", true), ",,");
+    }
+}
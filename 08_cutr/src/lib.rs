@@ -1,30 +1,115 @@
 use std::{
     error::Error,
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
     num::NonZeroUsize,
     ops::Range,
 };
 
-use clap::{Arg, ArgAction, Command};
-use csv::{ReaderBuilder, StringRecord, WriterBuilder};
-use regex::Regex;
+use clap::{parser::ValueSource, Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use csv::{ReaderBuilder, WriterBuilder};
+use regex::{bytes, Regex};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+mod layout;
+
+use layout::{extract_layout, Layout};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
+/// 0-based ranges of positions. An open-ended range (`N-`) ends at
+/// `usize::MAX`, and every range is cut down to the length of each line by
+/// `resolve`.
 type PositionList = Vec<Range<usize>>;
 
 #[derive(Debug)]
 pub enum Extract {
     Fields(PositionList),
+    /// Fields named in the header row, looked up in each file.
+    FieldNames(Vec<String>),
     Bytes(PositionList),
     Chars(PositionList),
+    /// Extended grapheme clusters (`-c` with `--graphemes`).
+    Graphemes(PositionList),
+    /// Terminal display columns (`-c` with `--columns`).
+    Columns(PositionList),
+    /// The character ranges of the columns picked from a `--layout`.
+    Layout(PositionList),
+}
+
+/// Where a line is split into fields.
+#[derive(Debug)]
+pub enum Splitter {
+    /// Every occurrence of a string of one or more bytes (`-d`).
+    Literal(Vec<u8>),
+    /// Every match of a regular expression (`--delim-regex`).
+    Regex(bytes::Regex),
+    /// Runs of whitespace, ignoring any at either end of the line, as awk
+    /// splits by default (`--whitespace`).
+    Whitespace,
+}
+
+impl Splitter {
+    /// The fields of `line`, or `None` if it has no separator at all.
+    fn split<'a>(&self, line: &'a [u8]) -> Option<Vec<&'a [u8]>> {
+        match self {
+            Self::Literal(delimiter) => {
+                let mut fields = vec![];
+                let (mut start, mut i) = (0, 0);
+                while i + delimiter.len() <= line.len() {
+                    if line[i..].starts_with(delimiter) {
+                        fields.push(&line[start..i]);
+                        i += delimiter.len();
+                        start = i;
+                    } else {
+                        i += 1;
+                    }
+                }
+                fields.push(&line[start..]);
+                (fields.len() > 1).then_some(fields)
+            }
+            Self::Regex(re) => re.is_match(line).then(|| re.split(line).collect()),
+            Self::Whitespace => line.iter().any(u8::is_ascii_whitespace).then(|| {
+                line.split(u8::is_ascii_whitespace)
+                    .filter(|field| !field.is_empty())
+                    .collect()
+            }),
+        }
+    }
+
+    /// What goes between the selected fields by default. A literal
+    /// delimiter is kept; separators that vary become a single space.
+    fn joiner(&self) -> &[u8] {
+        match self {
+            Self::Literal(delimiter) => delimiter,
+            Self::Regex(_) | Self::Whitespace => b" ",
+        }
+    }
+}
+
+/// How `--csv` reads quoted fields.
+#[derive(Debug)]
+pub struct CsvDialect {
+    delimiter: u8,
+    quote: u8,
+    escape: Option<u8>,
+    double_quote: bool,
+    comment: Option<u8>,
 }
 
 #[derive(Debug)]
 pub struct Config {
     files: Vec<String>,
-    delimiter: u8,
+    splitter: Splitter,
     extract: Extract,
+    csv: Option<CsvDialect>,
+    only_delimited: bool,
+    output_delimiter: Option<String>,
+    header: bool,
+    trim: bool,
+    no_split: bool,
+    complement: bool,
+    gnu: bool,
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -47,13 +132,61 @@ pub fn get_args() -> MyResult<Config> {
                 .help("Field delimiter")
                 .default_value("\t"),
         )
+        .arg(
+            Arg::new("delim_regex")
+                .value_name("REGEX")
+                .long("delim-regex")
+                .help("Split fields on matches of REGEX")
+                .requires("fields")
+                .conflicts_with_all(["delimiter", "bytes", "chars"]),
+        )
+        .arg(
+            Arg::new("whitespace")
+                .long("whitespace")
+                .help("Split fields on runs of whitespace, ignoring it at either end")
+                .requires("fields")
+                .conflicts_with_all(["delimiter", "delim_regex", "bytes", "chars"])
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("fields")
                 .value_name("FIELDS")
                 .short('f')
                 .long("fields")
                 .help("Selected fields")
-                .conflicts_with_all(&["chars", "bytes"]),
+                .allow_hyphen_values(true)
+                .conflicts_with_all(["chars", "bytes"]),
+        )
+        .arg(
+            Arg::new("field_names")
+                .value_name("NAMES")
+                .long("fields-by-name")
+                .help("Selected fields, by their names in the header row or layout")
+                .requires("name_source")
+                .conflicts_with_all(["fields", "chars", "bytes"]),
+        )
+        .arg(
+            Arg::new("header")
+                .long("header")
+                .help("Treat the first row as a header, which is always printed")
+                .conflicts_with_all(["chars", "bytes"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("layout")
+                .value_name("FILE")
+                .long("layout")
+                .help("Cut fixed-width columns named in FILE (NAME START WIDTH per line)")
+                .conflicts_with_all(["fields", "chars", "bytes", "header", "csv"]),
+        )
+        .group(ArgGroup::new("name_source").args(["header", "layout"]))
+        .arg(
+            Arg::new("trim")
+                .long("trim")
+                .help("Remove the spaces padding each --layout column")
+                .requires("layout")
+                .conflicts_with_all(["fields", "chars", "bytes"])
+                .action(ArgAction::SetTrue),
         )
         .arg(
             Arg::new("bytes")
@@ -61,7 +194,8 @@ pub fn get_args() -> MyResult<Config> {
                 .short('b')
                 .long("bytes")
                 .help("Selected bytes")
-                .conflicts_with_all(&["fields", "chars"]),
+                .allow_hyphen_values(true)
+                .conflicts_with_all(["fields", "chars"]),
         )
         .arg(
             Arg::new("chars")
@@ -69,53 +203,194 @@ pub fn get_args() -> MyResult<Config> {
                 .short('c')
                 .long("chars")
                 .help("Selected characters")
-                .conflicts_with_all(&["fields", "bytes"]),
+                .allow_hyphen_values(true)
+                .conflicts_with_all(["fields", "bytes"]),
+        )
+        .arg(
+            Arg::new("graphemes")
+                .long("graphemes")
+                .help("Count -c positions in user-perceived characters (grapheme clusters)")
+                .requires("chars")
+                .conflicts_with_all(["fields", "bytes"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("columns")
+                .long("columns")
+                .help("Count -c positions in display columns; wide characters take two")
+                .requires("chars")
+                .conflicts_with_all(["fields", "bytes", "graphemes"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no_split")
+                .short('n')
+                .help("Widen -b ranges so they don't split multibyte characters")
+                .requires("bytes")
+                .conflicts_with_all(["fields", "chars", "layout"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("complement")
+                .long("complement")
+                .help("Select everything except the given positions")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("gnu")
+                .long("gnu")
+                .help("Print each selected position once, in input order, as GNU cut does")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("only_delimited")
+                .short('s')
+                .long("only-delimited")
+                .help("Don't print lines without the delimiter")
+                .requires("fields")
+                .conflicts_with_all(["bytes", "chars"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("output_delimiter")
+                .value_name("STRING")
+                .long("output-delimiter")
+                .help("Join selected fields, or byte/character ranges, with STRING"),
+        )
+        .arg(
+            Arg::new("csv")
+                .long("csv")
+                .help("Parse fields as CSV, honoring quotes; the delimiter defaults to a comma")
+                .requires("fields")
+                .conflicts_with_all([
+                    "bytes",
+                    "chars",
+                    "delim_regex",
+                    "whitespace",
+                    "only_delimited",
+                    "output_delimiter",
+                ])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("quote")
+                .value_name("CHAR")
+                .long("quote")
+                .help("CSV quote character")
+                .default_value("\"")
+                .requires("csv"),
+        )
+        .arg(
+            Arg::new("escape")
+                .value_name("CHAR")
+                .long("escape")
+                .help("CSV escape character for quotes inside quoted fields")
+                .requires("csv"),
+        )
+        .arg(
+            Arg::new("no_double_quote")
+                .long("no-double-quote")
+                .help("Don't read doubled quotes in CSV fields as one quote")
+                .requires("csv")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("comment")
+                .value_name("CHAR")
+                .long("comment")
+                .help("Skip CSV lines starting with CHAR")
+                .requires("csv"),
         )
         .get_matches();
 
-    let delimiter = matches.get_one::<String>("delimiter").unwrap();
-    let delim_bytes = delimiter.as_bytes();
-    if delim_bytes.len() != 1 {
-        return Err(From::from(format!(
-            "--delim \"{}\" must be a single byte",
-            delimiter
-        )));
-    }
+    let splitter = if let Some(pattern) = matches.get_one::<String>("delim_regex") {
+        let re = bytes::Regex::new(pattern)
+            .map_err(|_| format!("Invalid --delim-regex \"{}\"", pattern))?;
+        if re.is_match(b"") {
+            return Err(From::from(format!(
+                "--delim-regex \"{}\" must not match an empty string",
+                pattern
+            )));
+        }
+        Splitter::Regex(re)
+    } else if matches.get_flag("whitespace") {
+        Splitter::Whitespace
+    } else {
+        match matches.get_one::<String>("delimiter").unwrap() {
+            delimiter if delimiter.is_empty() => {
+                return Err(From::from("--delim \"\" must not be empty"))
+            }
+            delimiter => Splitter::Literal(delimiter.as_bytes().to_vec()),
+        }
+    };
+
+    let complement = matches.get_flag("complement");
+    let gnu = matches.get_flag("gnu");
+    let select = |pos| select(pos, complement, gnu);
 
     let fields = matches
         .get_one::<String>("fields")
-        .map(|val| {
-            let s = val.as_str();
-            parse_pos(s)
-        })
+        .map(|val| parse_pos(val).map(select))
         .transpose()?;
 
     let bytes = matches
         .get_one::<String>("bytes")
-        .map(|val| {
-            let s = val.as_str();
-            parse_pos(s)
-        })
+        .map(|val| parse_pos(val).map(select))
         .transpose()?;
 
     let chars = matches
         .get_one::<String>("chars")
-        .map(|val| {
-            let s = val.as_str();
-            parse_pos(s)
-        })
+        .map(|val| parse_pos(val).map(select))
+        .transpose()?;
+
+    let field_names = matches
+        .get_one::<String>("field_names")
+        .map(|val| parse_names(val))
         .transpose()?;
 
     let extract = if let Some(field_pos) = fields {
         Extract::Fields(field_pos)
+    } else if let Some(path) = matches.get_one::<String>("layout") {
+        let layout = Layout::from_file(path)?;
+        let pos = match field_names {
+            Some(names) => find_names(&names, layout.names())?,
+            // Every column, as if given `1-`
+            None => parse_pos("1-")?,
+        };
+        Extract::Layout(layout.columns(&select(pos)))
+    } else if let Some(names) = field_names {
+        Extract::FieldNames(names)
     } else if let Some(byte_pos) = bytes {
         Extract::Bytes(byte_pos)
     } else if let Some(char_pos) = chars {
-        Extract::Chars(char_pos)
+        if matches.get_flag("graphemes") {
+            Extract::Graphemes(char_pos)
+        } else if matches.get_flag("columns") {
+            Extract::Columns(char_pos)
+        } else {
+            Extract::Chars(char_pos)
+        }
     } else {
         return Err(From::from("Must have --fields, --bytes, or --chars"));
     };
 
+    let csv = if matches.get_flag("csv") {
+        // CSV is comma-separated unless told otherwise, not tab-separated
+        let delimiter = match matches.value_source("delimiter") {
+            Some(ValueSource::DefaultValue) => b',',
+            _ => single_byte(&matches, "delimiter", "--delim")?.unwrap(),
+        };
+        Some(CsvDialect {
+            delimiter,
+            quote: single_byte(&matches, "quote", "--quote")?.unwrap(),
+            escape: single_byte(&matches, "escape", "--escape")?,
+            double_quote: !matches.get_flag("no_double_quote"),
+            comment: single_byte(&matches, "comment", "--comment")?,
+        })
+    } else {
+        None
+    };
+
     let files = matches
         .get_many::<String>("files")
         .unwrap()
@@ -124,83 +399,364 @@ pub fn get_args() -> MyResult<Config> {
 
     Ok(Config {
         files,
-        delimiter: *delim_bytes.first().unwrap(),
+        splitter,
         extract,
+        csv,
+        only_delimited: matches.get_flag("only_delimited"),
+        output_delimiter: matches.get_one::<String>("output_delimiter").cloned(),
+        header: matches.get_flag("header"),
+        trim: matches.get_flag("trim"),
+        no_split: matches.get_flag("no_split"),
+        complement,
+        gnu,
     })
 }
 
+fn parse_names(names: &str) -> MyResult<Vec<String>> {
+    names
+        .split(',')
+        .map(|name| match name {
+            "" => Err(From::from(format!("illegal list value: \"{}\"", names))),
+            name => Ok(name.to_string()),
+        })
+        .collect()
+}
+
+fn single_byte(matches: &ArgMatches, id: &str, flag: &str) -> MyResult<Option<u8>> {
+    match matches
+        .get_one::<String>(id)
+        .map(|val| (val, val.as_bytes()))
+    {
+        None => Ok(None),
+        Some((_, &[byte])) => Ok(Some(byte)),
+        Some((val, _)) => Err(From::from(format!(
+            "{} \"{}\" must be a single byte",
+            flag, val
+        ))),
+    }
+}
+
 pub fn run(config: Config) -> MyResult<()> {
+    let mut out = BufWriter::new(io::stdout());
+    // Byte and character ranges are run together unless told otherwise.
+    let separator = config.output_delimiter.as_deref().unwrap_or("");
     for filename in &config.files {
         match open(filename) {
             Err(err) => eprintln!("{}: {}", filename, err),
             Ok(file) => match &config.extract {
                 Extract::Fields(field_pos) => {
-                    let mut reader = ReaderBuilder::new()
-                        .delimiter(config.delimiter)
-                        .has_headers(false)
-                        .from_reader(file);
-
-                    let mut wtr = WriterBuilder::new()
-                        .delimiter(config.delimiter)
-                        .from_writer(io::stdout());
-
-                    for record in reader.records() {
-                        let record = record?;
-                        wtr.write_record(extract_fields(&record, field_pos))?;
-                    }
+                    cut_file(file, &mut out, &config, field_pos)?;
                 }
-                Extract::Bytes(bytes_pos) => {
-                    for line in file.lines() {
-                        println!("{}", extract_bytes(&line?, bytes_pos));
-                    }
+                Extract::FieldNames(names) => {
+                    let (file, field_pos) = find_header(file, &config, names)
+                        .map_err(|e| format!("{}: {}", filename, e))?;
+                    cut_file(file, &mut out, &config, &field_pos)?;
                 }
-                Extract::Chars(char_pos) => {
-                    for line in file.lines() {
-                        println!("{}", extract_chars(&line?, char_pos));
-                    }
+                Extract::Bytes(pos) => {
+                    for_each_line(file, |line| {
+                        let selected = extract_bytes(line, pos, separator, config.no_split);
+                        out.write_all(&selected)?;
+                        out.write_all(b"\n")?;
+                        Ok(())
+                    })?;
+                }
+                Extract::Chars(pos) => cut_lines(file, &mut out, pos, separator, extract_chars)?,
+                Extract::Graphemes(pos) => {
+                    cut_lines(file, &mut out, pos, separator, extract_graphemes)?
+                }
+                Extract::Columns(pos) => {
+                    cut_lines(file, &mut out, pos, separator, extract_columns)?
+                }
+                Extract::Layout(columns) => {
+                    let separator = config.output_delimiter.as_deref().unwrap_or("\t");
+                    cut_lines(
+                        file,
+                        &mut out,
+                        columns,
+                        separator,
+                        |line, columns, separator| {
+                            extract_layout(line, columns, separator, config.trim)
+                        },
+                    )?
                 }
             },
         }
     }
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes what `extract` selects from each line of `file`.
+fn cut_lines(
+    file: impl BufRead,
+    out: &mut impl Write,
+    pos: &[Range<usize>],
+    separator: &str,
+    extract: impl Fn(&str, &[Range<usize>], &str) -> String,
+) -> MyResult<()> {
+    for line in file.lines() {
+        writeln!(out, "{}", extract(&line?, pos, separator))?;
+    }
     Ok(())
 }
 
+/// Cuts the fields of every row in `file`, as CSV or as plain lines.
+fn cut_file(
+    file: impl BufRead,
+    out: &mut impl Write,
+    config: &Config,
+    field_pos: &[Range<usize>],
+) -> MyResult<()> {
+    if let Some(dialect) = &config.csv {
+        return cut_csv(file, out, dialect, field_pos);
+    }
+    let output_delimiter = config
+        .output_delimiter
+        .as_deref()
+        .map_or(config.splitter.joiner(), str::as_bytes);
+    let mut in_header = config.header;
+    for_each_line(file, |line| {
+        let only_delimited = config.only_delimited && !in_header;
+        in_header = false;
+        cut_fields(
+            line,
+            &config.splitter,
+            output_delimiter,
+            only_delimited,
+            field_pos,
+            out,
+        )
+    })
+}
+
+/// Reads the header row of `file` to find the columns in `names`. The row
+/// is put back in front of the rest, to be cut like any other.
+fn find_header(
+    mut file: Box<dyn BufRead>,
+    config: &Config,
+    names: &[String],
+) -> MyResult<(impl BufRead, PositionList)> {
+    let mut header = vec![];
+    file.read_until(b'\n', &mut header)?;
+    let field_pos = find_names(names, &header_fields(&header, config)?)?;
+    Ok((
+        io::Cursor::new(header).chain(file),
+        select(field_pos, config.complement, config.gnu),
+    ))
+}
+
+/// Calls `f` with each line of `file`, without its newline. Lines don't
+/// have to be valid UTF-8.
+fn for_each_line(mut file: impl BufRead, mut f: impl FnMut(&[u8]) -> MyResult<()>) -> MyResult<()> {
+    let mut line = vec![];
+    loop {
+        line.clear();
+        if file.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        f(line.strip_suffix(b"\n").unwrap_or(&line))?;
+    }
+}
+
+/// Splits with no notion of quoting, as GNU cut does, so a separator at
+/// the start of a line begins an empty first field, except with
+/// `--whitespace`. A line without any
+/// separator is printed whole, unless `only_delimited` drops it.
+fn cut_fields(
+    line: &[u8],
+    splitter: &Splitter,
+    output_delimiter: &[u8],
+    only_delimited: bool,
+    field_pos: &[Range<usize>],
+    out: &mut impl Write,
+) -> MyResult<()> {
+    if let Some(fields) = splitter.split(line) {
+        let selected = extract_fields(&fields, field_pos);
+        out.write_all(&selected.join(output_delimiter))?;
+    } else if only_delimited {
+        return Ok(());
+    } else {
+        out.write_all(line)?;
+    }
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Reads `file` as CSV in the given dialect and writes the selected fields
+/// back out as CSV, quoted as needed. Rows may have any number of fields;
+/// a row without any of the selected ones is written as `""`.
+fn cut_csv(
+    file: impl BufRead,
+    out: &mut impl Write,
+    dialect: &CsvDialect,
+    field_pos: &[Range<usize>],
+) -> MyResult<()> {
+    let mut reader = ReaderBuilder::new()
+        .delimiter(dialect.delimiter)
+        .quote(dialect.quote)
+        .escape(dialect.escape)
+        .double_quote(dialect.double_quote)
+        .comment(dialect.comment)
+        .flexible(true)
+        .has_headers(false)
+        .from_reader(file);
+
+    let mut wtr = WriterBuilder::new()
+        .delimiter(dialect.delimiter)
+        .quote(dialect.quote)
+        .double_quote(dialect.double_quote)
+        .escape(dialect.escape.unwrap_or(b'\\'))
+        .flexible(true)
+        .from_writer(out);
+
+    for record in reader.byte_records() {
+        let record = record?;
+        let fields: Vec<_> = record.iter().collect();
+        wtr.write_record(extract_fields(&fields, field_pos))?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// The fields of the first row of a file, split as every other row is. A
+/// CSV header has to fit on one line.
+fn header_fields(header: &[u8], config: &Config) -> MyResult<Vec<String>> {
+    let header = header.strip_suffix(b"\n").unwrap_or(header);
+    let fields = match &config.csv {
+        Some(dialect) => {
+            let mut record = csv::ByteRecord::new();
+            ReaderBuilder::new()
+                .delimiter(dialect.delimiter)
+                .quote(dialect.quote)
+                .escape(dialect.escape)
+                .double_quote(dialect.double_quote)
+                .has_headers(false)
+                .from_reader(header)
+                .read_byte_record(&mut record)?;
+            record.iter().map(<[u8]>::to_vec).collect()
+        }
+        None => match config.splitter.split(header) {
+            Some(fields) => fields.iter().map(|field| field.to_vec()).collect(),
+            None => vec![header.to_vec()],
+        },
+    };
+    Ok(fields
+        .iter()
+        .map(|field| String::from_utf8_lossy(field).into_owned())
+        .collect())
+}
+
+/// The position of each name among the header's `columns`, in the order
+/// the names were given. A name that appears twice means its first column.
+fn find_names(names: &[String], columns: &[String]) -> MyResult<PositionList> {
+    names
+        .iter()
+        .map(
+            |name| match columns.iter().position(|column| column == name) {
+                Some(i) => Ok(i..i + 1),
+                None => Err(From::from(format!(
+                    "Unknown column \"{}\"; available columns: {}",
+                    name,
+                    columns.join(", ")
+                ))),
+            },
+        )
+        .collect()
+}
+
 fn parse_pos(range: &str) -> MyResult<PositionList> {
-    let range_re = Regex::new(r"^(\d+)-(\d+)$").unwrap();
+    let range_re = Regex::new(r"^(\d*)-(\d*)$").unwrap();
     range
         .split(',')
-        .into_iter()
         .map(|val| {
             parse_index(val).map(|n| n..n + 1).or_else(|e| {
-                range_re.captures(val).ok_or(e).and_then(|captures| {
-                    let n1 = parse_index(&captures[1])?;
-                    let n2 = parse_index(&captures[2])?;
-                    if n1 >= n2 {
-                        return Err(format!(
-                            "First number in range ({}) must be lower than second number ({})",
-                            n1 + 1,
-                            n2 + 1
-                        ));
-                    }
-                    Ok(n1..n2 + 1)
-                })
+                range_re
+                    .captures(val)
+                    .filter(|captures| !captures[1].is_empty() || !captures[2].is_empty())
+                    .ok_or(e)
+                    .and_then(|captures| {
+                        let n1 = match &captures[1] {
+                            "" => 0,
+                            start => parse_index(start)?,
+                        };
+                        let n2 = match &captures[2] {
+                            "" => return Ok(n1..usize::MAX),
+                            end => parse_index(end)?,
+                        };
+                        if n1 > n2 {
+                            return Err(format!(
+                                "First number in range ({}) must not be greater than second number ({})",
+                                n1 + 1,
+                                n2 + 1
+                            ));
+                        }
+                        Ok(n1..n2 + 1)
+                    })
             })
         })
         .collect::<Result<_, _>>()
         .map_err(From::from)
 }
 
+/// Applies `--complement` or `--gnu` to the positions as given.
+fn select(pos: PositionList, complement: bool, gnu: bool) -> PositionList {
+    if complement {
+        self::complement(&pos)
+    } else if gnu {
+        normalize(&pos)
+    } else {
+        pos
+    }
+}
+
+/// Sorts the ranges and merges any that overlap or touch.
+fn normalize(pos: &[Range<usize>]) -> PositionList {
+    let mut sorted = pos.to_vec();
+    sorted.sort_by_key(|range| range.start);
+    let mut merged: PositionList = vec![];
+    for range in sorted {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Every position not in `pos`, in order.
+fn complement(pos: &[Range<usize>]) -> PositionList {
+    let mut start = 0;
+    let mut gaps = vec![];
+    for range in normalize(pos) {
+        if range.start > start {
+            gaps.push(start..range.start);
+        }
+        start = range.end;
+    }
+    if start < usize::MAX {
+        gaps.push(start..usize::MAX);
+    }
+    gaps
+}
+
+/// Cuts the ranges down to a line of `len` positions, dropping any that
+/// start past its end.
+fn resolve(pos: &[Range<usize>], len: usize) -> impl Iterator<Item = Range<usize>> + '_ {
+    pos.iter()
+        .filter(move |range| range.start < len)
+        .map(move |range| range.start..range.end.min(len))
+}
+
 fn parse_index(input: &str) -> Result<usize, String> {
     let value_error = || format!("illegal list value: \"{}\"", input);
-    input
-        .starts_with('+')
-        .then(|| Err(value_error()))
-        .unwrap_or_else(|| {
-            input
-                .parse::<NonZeroUsize>()
-                .map(|n| usize::from(n) - 1)
-                .map_err(|_| value_error())
-        })
+    if input.starts_with('+') {
+        Err(value_error())
+    } else {
+        input
+            .parse::<NonZeroUsize>()
+            .map(|n| usize::from(n) - 1)
+            .map_err(|_| value_error())
+    }
 }
 
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
@@ -210,38 +766,123 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     }
 }
 
-fn extract_chars(line: &str, char_pos: &[Range<usize>]) -> String {
-    let chars: Vec<_> = line.chars().collect();
-    char_pos
-        .iter()
-        .cloned()
-        .flat_map(|range| range.filter_map(|i| chars.get(i)))
-        .collect()
+/// The characters in each range, with `separator` between ranges.
+fn extract_chars(line: &str, char_pos: &[Range<usize>], separator: &str) -> String {
+    let chars: Vec<_> = line
+        .char_indices()
+        .map(|(i, c)| &line[i..i + c.len_utf8()])
+        .collect();
+    extract_text(&chars, char_pos, separator)
 }
 
-fn extract_bytes(line: &str, byte_pos: &[Range<usize>]) -> String {
-    let bytes = line.as_bytes();
-    let selected: Vec<_> = byte_pos
-        .iter()
-        .cloned()
-        .flat_map(|range| range.filter_map(|i| bytes.get(i)).copied())
+/// Like `extract_chars`, but a base character and the marks or joined
+/// characters that go with it count as one.
+fn extract_graphemes(line: &str, grapheme_pos: &[Range<usize>], separator: &str) -> String {
+    let graphemes: Vec<_> = line.graphemes(true).collect();
+    extract_text(&graphemes, grapheme_pos, separator)
+}
+
+fn extract_text(units: &[&str], pos: &[Range<usize>], separator: &str) -> String {
+    resolve(pos, units.len())
+        .map(|range| units[range].concat())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// The grapheme clusters displayed in each range of terminal columns, with
+/// `separator` between ranges. A wide character cut by the edge of a range
+/// leaves a space for each of its columns inside it, so the output is as
+/// wide as the range. Zero-width clusters go with the column they're in.
+fn extract_columns(line: &str, col_pos: &[Range<usize>], separator: &str) -> String {
+    let mut width = 0;
+    let cells: Vec<_> = line
+        .graphemes(true)
+        .map(|grapheme| {
+            let start = width;
+            width += grapheme.width();
+            (start..width, grapheme)
+        })
         .collect();
-    String::from_utf8_lossy(&selected).into_owned()
+    resolve(col_pos, width)
+        .map(|range| {
+            let mut selected = String::new();
+            for (cell, grapheme) in &cells {
+                if cell.is_empty() {
+                    if range.contains(&cell.start) {
+                        selected.push_str(grapheme);
+                    }
+                } else if range.start <= cell.start && cell.end <= range.end {
+                    selected.push_str(grapheme);
+                } else if cell.start < range.end && range.start < cell.end {
+                    let inside = cell.end.min(range.end) - cell.start.max(range.start);
+                    selected.push_str(&" ".repeat(inside));
+                }
+            }
+            selected
+        })
+        .collect::<Vec<_>>()
+        .join(separator)
 }
 
-fn extract_fields(record: &StringRecord, field_pos: &[Range<usize>]) -> Vec<String> {
-    field_pos
-        .iter()
-        .cloned()
-        .flat_map(|range| range.filter_map(|i| record.get(i)))
-        .map(String::from)
+/// The bytes in each range, with `separator` between ranges, exactly as
+/// they are in `line`. With `no_split`, each range is widened to take in
+/// the whole of any UTF-8 character it cuts through.
+fn extract_bytes(
+    line: &[u8],
+    byte_pos: &[Range<usize>],
+    separator: &str,
+    no_split: bool,
+) -> Vec<u8> {
+    let boundaries = if no_split {
+        char_boundaries(line)
+    } else {
+        vec![]
+    };
+    let widen = |range: Range<usize>| {
+        if !no_split {
+            return range;
+        }
+        let start = boundaries.partition_point(|&i| i <= range.start) - 1;
+        let end = boundaries.partition_point(|&i| i < range.end);
+        boundaries[start]..boundaries[end]
+    };
+    let selected: Vec<_> = resolve(byte_pos, line.len())
+        .map(|range| &line[widen(range)])
+        .collect();
+    selected.join(separator.as_bytes())
+}
+
+/// The offset of every character in `line`, and its length. A byte that
+/// isn't part of a valid UTF-8 sequence counts as a character of its own.
+fn char_boundaries(line: &[u8]) -> Vec<usize> {
+    let mut boundaries = vec![];
+    let mut offset = 0;
+    for chunk in line.utf8_chunks() {
+        let valid = chunk.valid();
+        boundaries.extend(valid.char_indices().map(|(i, _)| offset + i));
+        offset += valid.len();
+        boundaries.extend(offset..offset + chunk.invalid().len());
+        offset += chunk.invalid().len();
+    }
+    boundaries.push(offset);
+    boundaries
+}
+
+fn extract_fields<'a, T: ?Sized>(fields: &[&'a T], field_pos: &[Range<usize>]) -> Vec<&'a T> {
+    resolve(field_pos, fields.len())
+        .flat_map(|range| &fields[range])
+        .copied()
         .collect()
 }
 
 #[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
 mod unit_tests {
-    use super::{extract_bytes, extract_chars, extract_fields, parse_pos};
-    use csv::StringRecord;
+    use super::{
+        bytes, char_boundaries, complement, cut_fields, extract_bytes, extract_chars,
+        extract_columns, extract_fields, extract_graphemes, find_names, normalize, parse_names,
+        parse_pos, Splitter,
+    };
 
     #[test]
     fn test_parse_pos() {
@@ -297,28 +938,26 @@ mod unit_tests {
         let res = parse_pos("1,");
         assert!(res.is_err());
 
-        let res = parse_pos("1-");
-        assert!(res.is_err());
-
         let res = parse_pos("1-1-1");
         assert!(res.is_err());
 
         let res = parse_pos("1-1-a");
         assert!(res.is_err());
 
-        // First number must be less than second
-        let res = parse_pos("1-1");
+        let res = parse_pos("1,-");
         assert!(res.is_err());
-        assert_eq!(
-            res.unwrap_err().to_string(),
-            "First number in range (1) must be lower than second number (1)"
-        );
+        assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"-\"",);
+
+        let res = parse_pos("-0");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"0\"",);
 
+        // First number must not be greater than second
         let res = parse_pos("2-1");
         assert!(res.is_err());
         assert_eq!(
             res.unwrap_err().to_string(),
-            "First number in range (2) must be lower than second number (1)"
+            "First number in range (2) must not be greater than second number (1)"
         );
 
         // All the following are acceptable
@@ -353,11 +992,41 @@ mod unit_tests {
         let res = parse_pos("15,19-20");
         assert!(res.is_ok());
         assert_eq!(res.unwrap(), vec![14..15, 18..20]);
+
+        let res = parse_pos("3-3");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![2..3]);
+
+        let res = parse_pos("3-");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![2..usize::MAX]);
+
+        let res = parse_pos("-2,5");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..2, 4..5]);
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert!(normalize(&[]).is_empty());
+        assert_eq!(normalize(&[2..3, 0..1]), vec![0..1, 2..3]);
+        assert_eq!(normalize(&[0..1, 0..1]), vec![0..1]);
+        assert_eq!(normalize(&[0..2, 2..3, 5..6]), vec![0..3, 5..6]);
+        assert_eq!(normalize(&[4..usize::MAX, 0..5]), vec![0..usize::MAX]);
+    }
+
+    #[test]
+    fn test_complement() {
+        assert_eq!(complement(&[]), vec![0..usize::MAX]);
+        assert_eq!(complement(&[1..2]), vec![0..1, 2..usize::MAX]);
+        assert_eq!(complement(&[2..3, 0..1]), vec![1..2, 3..usize::MAX]);
+        assert!(complement(&[0..usize::MAX]).is_empty());
+        assert_eq!(complement(&[0..2, 3..usize::MAX]), vec![2..3]);
     }
 
     #[test]
     fn test_extract_fields() {
-        let rec = StringRecord::from(vec!["Captain", "Sham", "12345"]);
+        let rec = ["Captain", "Sham", "12345"];
         assert_eq!(extract_fields(&rec, &[0..1]), &["Captain"]);
         assert_eq!(extract_fields(&rec, &[1..2]), &["Sham"]);
         assert_eq!(extract_fields(&rec, &[0..1, 2..3]), &["Captain", "12345"]);
@@ -365,23 +1034,162 @@ mod unit_tests {
         assert_eq!(extract_fields(&rec, &[1..2, 0..1]), &["Sham", "Captain"]);
     }
 
+    #[test]
+    fn test_cut_fields() {
+        let cut = |line: &str, field_pos| {
+            let mut out = vec![];
+            let comma = Splitter::Literal(b",".to_vec());
+            cut_fields(line.as_bytes(), &comma, b",", false, field_pos, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(cut("a,b,c", &[1..2]), "b\n");
+        assert_eq!(cut("a,\"b,c\",d", &[1..3]), "\"b,c\"\n");
+        assert_eq!(cut("a,,c", &[0..2]), "a,\n");
+        assert_eq!(cut("a,b", &[2..3]), "\n");
+        assert_eq!(cut("abc", &[1..2]), "abc\n");
+        assert_eq!(cut("", &[0..1]), "\n");
+
+        let mut out = vec![];
+        let comma = Splitter::Literal(b",".to_vec());
+        cut_fields(b"a,b,c", &comma, b" | ", true, &[0..1, 2..3], &mut out).unwrap();
+        cut_fields(b"abc", &comma, b" | ", true, &[0..1], &mut out).unwrap();
+        assert_eq!(out, b"a | c\n");
+    }
+
+    #[test]
+    fn test_find_names() {
+        let columns = ["a".to_string(), "b".to_string(), "a".to_string()];
+        let names = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            find_names(&names(&["b", "a"]), &columns).unwrap(),
+            [1..2, 0..1]
+        );
+        assert_eq!(
+            find_names(&names(&["c"]), &columns)
+                .unwrap_err()
+                .to_string(),
+            "Unknown column \"c\"; available columns: a, b, a"
+        );
+        assert!(parse_names("a,,b").is_err());
+        assert_eq!(parse_names("a b,c").unwrap(), ["a b", "c"]);
+    }
+
+    #[test]
+    fn test_split() {
+        let split = |splitter: &Splitter, line: &str| {
+            splitter.split(line.as_bytes()).map(|fields| {
+                fields
+                    .iter()
+                    .map(|field| String::from_utf8_lossy(field).into_owned())
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        let colons = Splitter::Literal(b"::".to_vec());
+        assert_eq!(split(&colons, "a::b::c").unwrap(), ["a", "b", "c"]);
+        assert_eq!(split(&colons, "::a:b::").unwrap(), ["", "a:b", ""]);
+        assert_eq!(split(&colons, "a:::b").unwrap(), ["a", ":b"]);
+        assert!(split(&colons, "a:b").is_none());
+
+        let spaces = Splitter::Regex(bytes::Regex::new(r"\s+").unwrap());
+        assert_eq!(split(&spaces, "a  b\tc").unwrap(), ["a", "b", "c"]);
+        assert_eq!(split(&spaces, "  a b").unwrap(), ["", "a", "b"]);
+        assert!(split(&spaces, "ab").is_none());
+
+        let ws = Splitter::Whitespace;
+        assert_eq!(split(&ws, "  a  b\tc ").unwrap(), ["a", "b", "c"]);
+        assert_eq!(split(&ws, " a").unwrap(), ["a"]);
+        assert!(split(&ws, "ab").is_none());
+    }
+
     #[test]
     fn test_extract_chars() {
-        assert_eq!(extract_chars("", &[0..1]), "".to_string());
-        assert_eq!(extract_chars("ábc", &[0..1]), "á".to_string());
-        assert_eq!(extract_chars("ábc", &[0..1, 2..3]), "ác".to_string());
-        assert_eq!(extract_chars("ábc", &[0..3]), "ábc".to_string());
-        assert_eq!(extract_chars("ábc", &[2..3, 1..2]), "cb".to_string());
-        assert_eq!(extract_chars("ábc", &[0..1, 1..2, 4..5]), "áb".to_string());
+        assert_eq!(extract_chars("", &[0..1], ""), "".to_string());
+        assert_eq!(extract_chars("ábc", &[0..1], ""), "á".to_string());
+        assert_eq!(extract_chars("ábc", &[0..1, 2..3], ""), "ác".to_string());
+        assert_eq!(extract_chars("ábc", &[0..3], ""), "ábc".to_string());
+        assert_eq!(extract_chars("ábc", &[2..3, 1..2], ""), "cb".to_string());
+        assert_eq!(
+            extract_chars("ábc", &[0..1, 1..2, 4..5], ""),
+            "áb".to_string()
+        );
+        assert_eq!(extract_chars("ábc", &[1..usize::MAX], ""), "bc".to_string());
+        assert_eq!(
+            extract_chars("ábc", &[0..usize::MAX, 0..1], ""),
+            "ábcá".to_string()
+        );
+        assert_eq!(extract_chars("ábc", &[3..usize::MAX], ""), "".to_string());
+        assert_eq!(extract_chars("ábc", &[0..2, 2..3], ":"), "áb:c".to_string());
+        assert_eq!(extract_chars("ábc", &[0..1, 5..6], ":"), "á".to_string());
+    }
+
+    #[test]
+    fn test_extract_graphemes() {
+        assert_eq!(extract_graphemes("", &[0..1], ""), "");
+        assert_eq!(extract_graphemes("e\u{301}tre", &[0..1], ""), "e\u{301}");
+        assert_eq!(
+            extract_graphemes("e\u{301}tre", &[1..usize::MAX], ""),
+            "tre"
+        );
+        assert_eq!(
+            extract_graphemes("a\u{1f469}\u{200d}\u{1f4bb}b", &[1..2, 2..3], ":"),
+            "\u{1f469}\u{200d}\u{1f4bb}:b"
+        );
+    }
+
+    #[test]
+    fn test_extract_columns() {
+        assert_eq!(extract_columns("", &[0..1], ""), "");
+        assert_eq!(extract_columns("ab漢字", &[0..4], ""), "ab漢");
+        assert_eq!(extract_columns("ab漢字", &[2..usize::MAX], ""), "漢字");
+        assert_eq!(extract_columns("ab漢字", &[1..3], ""), "b ");
+        assert_eq!(extract_columns("ab漢字", &[3..5], ""), "  ");
+        assert_eq!(
+            extract_columns("a\u{301}b", &[0..1, 1..2], "|"),
+            "a\u{301}|b"
+        );
+        assert_eq!(extract_columns("ab", &[2..3], ""), "");
     }
 
     #[test]
     fn test_extract_bytes() {
-        assert_eq!(extract_bytes("ábc", &[0..1]), "�".to_string());
-        assert_eq!(extract_bytes("ábc", &[0..2]), "á".to_string());
-        assert_eq!(extract_bytes("ábc", &[0..3]), "áb".to_string());
-        assert_eq!(extract_bytes("ábc", &[0..4]), "ábc".to_string());
-        assert_eq!(extract_bytes("ábc", &[3..4, 2..3]), "cb".to_string());
-        assert_eq!(extract_bytes("ábc", &[0..2, 5..6]), "á".to_string());
+        let line = "ábc".as_bytes();
+        assert_eq!(extract_bytes(line, &[0..1], "", false), b"\xc3");
+        assert_eq!(extract_bytes(line, &[0..2], "", false), "á".as_bytes());
+        assert_eq!(extract_bytes(line, &[0..3], "", false), "áb".as_bytes());
+        assert_eq!(extract_bytes(line, &[0..4], "", false), "ábc".as_bytes());
+        assert_eq!(extract_bytes(line, &[3..4, 2..3], "", false), b"cb");
+        assert_eq!(
+            extract_bytes(line, &[0..2, 5..6], "", false),
+            "á".as_bytes()
+        );
+        assert_eq!(
+            extract_bytes(line, &[0..2, 3..4], ":", false),
+            "á:c".as_bytes()
+        );
+        assert_eq!(extract_bytes(b"Mis\xe9rables", &[3..4], "", false), b"\xe9");
+
+        // -n widens ranges to whole characters
+        assert_eq!(extract_bytes(line, &[0..1], "", true), "á".as_bytes());
+        assert_eq!(extract_bytes(line, &[1..2], "", true), "á".as_bytes());
+        assert_eq!(extract_bytes(line, &[1..3], "", true), "áb".as_bytes());
+        assert_eq!(
+            extract_bytes("a€b".as_bytes(), &[2..3], "", true),
+            "€".as_bytes()
+        );
+        assert_eq!(extract_bytes(b"a\xe9b", &[1..2], "", true), b"\xe9");
+        assert_eq!(extract_bytes(b"\xe2\x82", &[1..2], "", true), b"\x82");
+    }
+
+    #[test]
+    fn test_char_boundaries() {
+        assert_eq!(char_boundaries(b""), [0]);
+        assert_eq!(char_boundaries("ábc".as_bytes()), [0, 2, 3, 4]);
+        assert_eq!(char_boundaries(b"a\xe9\xe2\x82\xacb"), [0, 1, 2, 5, 6]);
     }
 }
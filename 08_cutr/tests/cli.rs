@@ -9,6 +9,14 @@ const PRG: &str = "cutr";
 const CSV: &str = "tests/inputs/movies1.csv";
 const TSV: &str = "tests/inputs/movies1.tsv";
 const BOOKS: &str = "tests/inputs/books.tsv";
+const QUOTED: &str = "tests/inputs/quoted.csv";
+const MIXED: &str = "tests/inputs/mixed.tsv";
+const COLONS: &str = "tests/inputs/colons.txt";
+const PS: &str = "tests/inputs/ps.txt";
+const UNICODE: &str = "tests/inputs/unicode.txt";
+const ACCOUNTS: &str = "tests/inputs/accounts.dat";
+const LAYOUT: &str = "tests/inputs/accounts.layout";
+const LATIN1: &str = "tests/inputs/latin1.txt";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -35,7 +43,7 @@ fn skips_bad_file() -> TestResult {
     let bad = gen_bad_file();
     let expected = format!("{}: .* [(]os error 2[)]", bad);
     Command::cargo_bin(PRG)?
-        .args(&["-f", "1", CSV, &bad, TSV])
+        .args(["-f", "1", CSV, &bad, TSV])
         .assert()
         .success()
         .stderr(predicate::str::is_match(expected)?);
@@ -93,7 +101,7 @@ fn dies_bad_digit_chars() -> TestResult {
 fn dies_empty_delimiter() -> TestResult {
     dies(
         &[CSV, "-f", "1", "-d", ""],
-        "--delim \"\" must be a single byte",
+        "--delim \"\" must not be empty",
     )
 }
 
@@ -101,7 +109,7 @@ fn dies_empty_delimiter() -> TestResult {
 #[test]
 fn dies_bad_delimiter() -> TestResult {
     dies(
-        &[CSV, "-f", "1", "-d", ",,"],
+        &[CSV, "--csv", "-f", "1", "-d", ",,"],
         "--delim \",,\" must be a single byte",
     )
 }
@@ -110,7 +118,7 @@ fn dies_bad_delimiter() -> TestResult {
 #[test]
 fn dies_chars_bytes_fields() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&[CSV, "-c", "1", "-f", "1", "-b", "1"])
+        .args([CSV, "-c", "1", "-f", "1", "-b", "1"])
         .assert()
         .failure();
     Ok(())
@@ -120,7 +128,7 @@ fn dies_chars_bytes_fields() -> TestResult {
 #[test]
 fn dies_bytes_fields() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&[CSV, "-f", "1", "-b", "1"])
+        .args([CSV, "-f", "1", "-b", "1"])
         .assert()
         .failure();
     Ok(())
@@ -130,7 +138,7 @@ fn dies_bytes_fields() -> TestResult {
 #[test]
 fn dies_chars_fields() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&[CSV, "-c", "1", "-f", "1"])
+        .args([CSV, "-c", "1", "-f", "1"])
         .assert()
         .failure();
     Ok(())
@@ -140,7 +148,7 @@ fn dies_chars_fields() -> TestResult {
 #[test]
 fn dies_chars_bytes() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&[CSV, "-c", "1", "-b", "1"])
+        .args([CSV, "-c", "1", "-b", "1"])
         .assert()
         .failure();
     Ok(())
@@ -159,14 +167,13 @@ fn run(args: &[&str], expected_file: &str) -> TestResult {
 }
 
 // --------------------------------------------------
-fn run_lossy(args: &[&str], expected_file: &str) -> TestResult {
-    let contents = fs::read(expected_file)?;
-    let expected = String::from_utf8_lossy(&contents);
+fn run_bytes(args: &[&str], expected_file: &str) -> TestResult {
+    let expected = fs::read(expected_file)?;
     Command::cargo_bin(PRG)?
         .args(args)
         .assert()
         .success()
-        .stdout(predicate::str::contains(expected));
+        .stdout(expected);
     Ok(())
 }
 
@@ -275,7 +282,7 @@ fn tsv_b2() -> TestResult {
 // --------------------------------------------------
 #[test]
 fn tsv_b8() -> TestResult {
-    run_lossy(&[TSV, "-b", "8"], "tests/expected/movies1.tsv.b8.out")
+    run_bytes(&[TSV, "-b", "8"], "tests/expected/movies1.tsv.b8.out")
 }
 
 // --------------------------------------------------
@@ -293,7 +300,7 @@ fn tsv_b2_3() -> TestResult {
 // --------------------------------------------------
 #[test]
 fn tsv_b1_8() -> TestResult {
-    run_lossy(&[TSV, "-b", "1-8"], "tests/expected/movies1.tsv.b1-8.out")
+    run_bytes(&[TSV, "-b", "1-8"], "tests/expected/movies1.tsv.b1-8.out")
 }
 
 // --------------------------------------------------
@@ -337,3 +344,667 @@ fn tsv_c1_8() -> TestResult {
 fn repeated_value() -> TestResult {
     run(&[BOOKS, "-c", "1,1"], "tests/expected/books.c1,1.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn quoted_f2() -> TestResult {
+    run(
+        &[QUOTED, "-f", "2", "-d", ","],
+        "tests/expected/quoted.csv.f2.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn quoted_f2_3() -> TestResult {
+    run(
+        &[QUOTED, "-f", "2-3", "-d", ","],
+        "tests/expected/quoted.csv.f2-3.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn quoted_f1_3() -> TestResult {
+    run(
+        &[QUOTED, "-f", "1-3", "-d", ","],
+        "tests/expected/quoted.csv.f1-3.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_mode_quoted() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([QUOTED, "--csv", "-f", "2,3", "-d", ","])
+        .assert()
+        .success()
+        .stdout(
+            "quote,year\n\
+             \"Hello, world\",1999\n\
+             \"She said \"\"hi\"\"\",2001\n\
+             \"\"\n\
+             plain,2003\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_mode_default_delim() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--csv", "-f", "2"])
+        .write_stdin("a,\"b,c\",d\n")
+        .assert()
+        .success()
+        .stdout("\"b,c\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_mode_dialect() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([
+            "--csv",
+            "-d",
+            ";",
+            "--quote",
+            "'",
+            "--comment",
+            "#",
+            "-f",
+            "2",
+        ])
+        .write_stdin("a;'b;c';d\n#x;y\ne;f\n")
+        .assert()
+        .success()
+        .stdout("'b;c'\nf\n");
+
+    Command::cargo_bin(PRG)?
+        .args([
+            "--csv",
+            "-d",
+            ",",
+            "--escape",
+            "\\",
+            "--no-double-quote",
+            "-f",
+            "1",
+        ])
+        .write_stdin("\"say \\\"hi\\\", ok\",x\n")
+        .assert()
+        .success()
+        .stdout("\"say \\\"hi\\\", ok\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_csv_options() -> TestResult {
+    dies(
+        &[CSV, "--csv", "-f", "1", "--quote", "''"],
+        "--quote \"''\" must be a single byte",
+    )?;
+    dies(&[CSV, "-f", "1", "--quote", "'"], "--csv")?;
+    dies(&[CSV, "--csv", "-c", "1"], "cannot be used with")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_open() -> TestResult {
+    run(&[TSV, "-f", "2-"], "tests/expected/movies1.tsv.f2-.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f_open_2() -> TestResult {
+    run(&[TSV, "-f", "-2"], "tests/expected/movies1.tsv.f-2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_2() -> TestResult {
+    run(&[TSV, "-f", "2-2"], "tests/expected/movies1.tsv.f2-2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_c2_open() -> TestResult {
+    run(&[CSV, "-c", "2-"], "tests/expected/movies1.csv.c2-.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_c_open_2() -> TestResult {
+    run(&[CSV, "-c", "-2"], "tests/expected/movies1.csv.c-2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_b2_open() -> TestResult {
+    run(&[CSV, "-b", "2-"], "tests/expected/movies1.csv.b2-.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_complement() -> TestResult {
+    run(
+        &[TSV, "-f", "2", "--complement"],
+        "tests/expected/movies1.tsv.f2.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f1_3_complement() -> TestResult {
+    run(
+        &[TSV, "-f", "3,1", "--complement"],
+        "tests/expected/movies1.tsv.f1,3.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_open_complement() -> TestResult {
+    run(
+        &[TSV, "-f", "2-", "--complement"],
+        "tests/expected/movies1.tsv.f2-.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_c1_3_complement() -> TestResult {
+    run(
+        &[CSV, "-c", "1,3", "--complement"],
+        "tests/expected/movies1.csv.c1,3.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_decreasing_range() -> TestResult {
+    dies(
+        &[CSV, "-f", "3-2"],
+        "First number in range (3) must not be greater than second number (2)",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f3_1_gnu() -> TestResult {
+    run(
+        &[TSV, "--gnu", "-f", "3,1"],
+        "tests/expected/movies1.tsv.f3,1.gnu.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_1_2_gnu() -> TestResult {
+    run(
+        &[TSV, "--gnu", "-f", "2,1-2"],
+        "tests/expected/movies1.tsv.f2,1-2.gnu.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f3_2_open_gnu() -> TestResult {
+    run(
+        &[TSV, "--gnu", "-f", "3,2-"],
+        "tests/expected/movies1.tsv.f3,2-.gnu.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_c3_1_gnu() -> TestResult {
+    run(
+        &[CSV, "--gnu", "-c", "3,1"],
+        "tests/expected/movies1.csv.c3,1.gnu.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_c2_1_2_gnu() -> TestResult {
+    run(
+        &[CSV, "--gnu", "-c", "2,1-2"],
+        "tests/expected/movies1.csv.c2,1-2.gnu.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_f1() -> TestResult {
+    run(&[MIXED, "-f", "1"], "tests/expected/mixed.tsv.f1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_f2() -> TestResult {
+    run(&[MIXED, "-f", "2"], "tests/expected/mixed.tsv.f2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_f1_3() -> TestResult {
+    run(&[MIXED, "-f", "1,3"], "tests/expected/mixed.tsv.f1,3.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_f1_only_delimited() -> TestResult {
+    run(
+        &[MIXED, "-s", "-f", "1"],
+        "tests/expected/mixed.tsv.f1.only-delimited.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_f2_only_delimited() -> TestResult {
+    run(
+        &[MIXED, "--only-delimited", "-f", "2"],
+        "tests/expected/mixed.tsv.f2.only-delimited.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_f1_3_only_delimited() -> TestResult {
+    run(
+        &[MIXED, "-s", "-f", "1,3"],
+        "tests/expected/mixed.tsv.f1,3.only-delimited.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f1_3_output_delimiter() -> TestResult {
+    run(
+        &[TSV, "-f", "1,3", "--output-delimiter", " | "],
+        "tests/expected/movies1.tsv.f1,3.odelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f1_open_output_delimiter() -> TestResult {
+    run(
+        &[CSV, "-d", ",", "-f", "1-", "--output-delimiter", "\t"],
+        "tests/expected/movies1.csv.f1-.odelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_c1_2_4_open_output_delimiter() -> TestResult {
+    run(
+        &[CSV, "-c", "1-2,4-", "--output-delimiter", ":"],
+        "tests/expected/movies1.csv.c1-2,4-.odelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_b1_3_5_output_delimiter() -> TestResult {
+    run(
+        &[CSV, "-b", "1,3,5", "--output-delimiter", "::"],
+        "tests/expected/movies1.csv.b1,3,5.odelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_only_delimited_chars() -> TestResult {
+    dies(&[CSV, "-s", "-c", "1"], "cannot be used with")
+}
+
+// --------------------------------------------------
+#[test]
+fn multibyte_delimiter() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([COLONS, "-d", "::", "-f", "1,3"])
+        .assert()
+        .success()
+        .stdout("name::shell\n::/bin/sh\nalice::/bin/bash\nno separators\n");
+
+    Command::cargo_bin(PRG)?
+        .args([
+            COLONS,
+            "-d",
+            "::",
+            "-s",
+            "-f",
+            "2",
+            "--output-delimiter",
+            ",",
+        ])
+        .assert()
+        .success()
+        .stdout("uid\n0\n1000\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn delim_regex() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([PS, "--delim-regex", r"\s+", "-f", "2,5"])
+        .assert()
+        .success()
+        .stdout("PID CMD\n1 init\n1234 bash\n\n");
+
+    Command::cargo_bin(PRG)?
+        .args([
+            COLONS,
+            "--delim-regex",
+            ":+",
+            "-f",
+            "1-2",
+            "--output-delimiter",
+            "\t",
+        ])
+        .assert()
+        .success()
+        .stdout("name\tuid\n\t0\nalice\t1000\nno separators\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn whitespace() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([PS, "--whitespace", "-f", "1,4"])
+        .assert()
+        .success()
+        .stdout("PID CMD\n1 init\n1234 bash\n\n");
+
+    Command::cargo_bin(PRG)?
+        .args([
+            PS,
+            "--whitespace",
+            "-s",
+            "-f",
+            "3-",
+            "--output-delimiter",
+            ",",
+        ])
+        .assert()
+        .success()
+        .stdout("TIME,CMD\n00:00:01,init\n00:00:00,bash\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_delim_regex() -> TestResult {
+    dies(
+        &[TSV, "--delim-regex", "[a-", "-f", "1"],
+        "Invalid --delim-regex \"[a-\"",
+    )?;
+    dies(
+        &[TSV, "--delim-regex", "x*", "-f", "1"],
+        "--delim-regex \"x*\" must not match an empty string",
+    )?;
+    dies(&[TSV, "--whitespace", "-c", "1"], "cannot be used with")?;
+    dies(
+        &[TSV, "--whitespace", "-d", ",", "-f", "1"],
+        "cannot be used with",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fields_by_name() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([
+            TSV,
+            "tests/inputs/movies3.tsv",
+            "--header",
+            "--fields-by-name",
+            "title,director",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "title\tdirector\n\
+             The Blues Brothers\tJohn Landis\n\
+             Les Misérables\tTom Hooper\n\
+             title\tdirector\n\
+             The Blues Brothers\tJohn Landis\n\
+             Les Misérables\tTom Hooper\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn fields_by_name_csv() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([
+            QUOTED,
+            "--csv",
+            "-d",
+            ",",
+            "--header",
+            "--fields-by-name",
+            "quote",
+        ])
+        .assert()
+        .success()
+        .stdout("quote\n\"Hello, world\"\n\"She said \"\"hi\"\"\"\n\"\"\nplain\n");
+
+    Command::cargo_bin(PRG)?
+        .args([
+            CSV,
+            "-d",
+            ",",
+            "--header",
+            "--fields-by-name",
+            "year",
+            "--complement",
+        ])
+        .assert()
+        .success()
+        .stdout("title,director\nThe Blues Brothers,John Landis\nLes Misérables,Tom Hooper\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn header_only_delimited() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([MIXED, "--header", "-s", "-f", "2"])
+        .assert()
+        .success()
+        .stdout("# movies, one per line\nyear\n1980\n2019\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_unknown_field_name() -> TestResult {
+    dies(
+        &[TSV, "--header", "--fields-by-name", "title,rating"],
+        &format!(
+            "{}: Unknown column \"rating\"; available columns: title, year, director",
+            TSV
+        ),
+    )?;
+    dies(&[TSV, "--fields-by-name", "title"], "--header")?;
+    dies(
+        &[TSV, "--header", "--fields-by-name", "title,"],
+        "illegal list value: \"title,\"",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn graphemes() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([UNICODE, "-c", "1"])
+        .assert()
+        .success()
+        .stdout("e\n\u{1f468}\n漢\n\u{1f1eb}\n");
+
+    Command::cargo_bin(PRG)?
+        .args([UNICODE, "-c", "1", "--graphemes"])
+        .assert()
+        .success()
+        .stdout(
+            "e\u{301}\n\
+             \u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}\n\
+             漢\n\
+             \u{1f1eb}\u{1f1f7}\n",
+        );
+
+    Command::cargo_bin(PRG)?
+        .args([UNICODE, "-c", "2-3", "--graphemes"])
+        .assert()
+        .success()
+        .stdout("te\u{301}\n f\n字a\nx\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn columns() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([UNICODE, "-c", "3-", "--columns"])
+        .assert()
+        .success()
+        .stdout("e\u{301}\n family\n字ab\nx\n");
+
+    // Wide characters cut in half leave a space
+    Command::cargo_bin(PRG)?
+        .args([UNICODE, "-c", "1,3", "--columns", "--output-delimiter", "|"])
+        .assert()
+        .success()
+        .stdout("e\u{301}|e\u{301}\n | \n | \n |x\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_graphemes_columns() -> TestResult {
+    dies(
+        &[UNICODE, "-c", "1", "--graphemes", "--columns"],
+        "cannot be used with",
+    )?;
+    dies(&[UNICODE, "-b", "1", "--columns"], "cannot be used with")
+}
+
+// --------------------------------------------------
+#[test]
+fn layout() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([ACCOUNTS, "--layout", LAYOUT])
+        .assert()
+        .success()
+        .stdout(
+            "0001\tÉmile Zola      \t1865\t0042.50\n\
+             0002\tSamuel Beckett  \t1952\t1200.00\n\
+             0003\tJules Verne     \t1870\t   7.25\n\
+             0004\tShort\t\t\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn layout_by_name_trimmed() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([
+            ACCOUNTS,
+            "--layout",
+            LAYOUT,
+            "--fields-by-name",
+            "balance,name",
+            "--trim",
+            "--output-delimiter",
+            ",",
+        ])
+        .assert()
+        .success()
+        .stdout("0042.50,Émile Zola\n1200.00,Samuel Beckett\n7.25,Jules Verne\n,Short\n");
+
+    Command::cargo_bin(PRG)?
+        .args([
+            ACCOUNTS,
+            "--layout",
+            LAYOUT,
+            "--fields-by-name",
+            "name,year",
+            "--complement",
+        ])
+        .assert()
+        .success()
+        .stdout("0001\t0042.50\n0002\t1200.00\n0003\t   7.25\n0004\t\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_layout() -> TestResult {
+    dies(
+        &[ACCOUNTS, "--layout", LAYOUT, "--fields-by-name", "nope"],
+        "Unknown column \"nope\"; available columns: id, name, year, balance",
+    )?;
+    dies(
+        &[ACCOUNTS, "--layout", CSV],
+        &format!("{}: line 1: expected NAME START WIDTH", CSV),
+    )?;
+    dies(
+        &[ACCOUNTS, "--layout", LAYOUT, "-c", "1"],
+        "cannot be used with",
+    )?;
+    dies(&[ACCOUNTS, "-c", "1", "--trim"], "cannot be used with")?;
+    dies(&[ACCOUNTS, "--trim"], "--layout")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_b5() -> TestResult {
+    run_bytes(&[LATIN1, "-b", "5"], "tests/expected/latin1.txt.b5.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_b1_8() -> TestResult {
+    run_bytes(&[LATIN1, "-b", "1-8"], "tests/expected/latin1.txt.b1-8.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_b5_open() -> TestResult {
+    run_bytes(&[LATIN1, "-b", "5-"], "tests/expected/latin1.txt.b5-.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn no_split() -> TestResult {
+    // Byte 9 is the second byte of "é"
+    Command::cargo_bin(PRG)?
+        .args([TSV, "-n", "-b", "9-10"])
+        .assert()
+        .success()
+        .stdout("ar\ns \nér\n");
+
+    // Bytes that aren't UTF-8 are characters of their own
+    Command::cargo_bin(PRG)?
+        .args([LATIN1, "-n", "-b", "5,8"])
+        .assert()
+        .success()
+        .stdout(&b"M\xe9\nrt\ne\n"[..]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_no_split_chars() -> TestResult {
+    dies(&[TSV, "-n", "-c", "1"], "cannot be used with")
+}
@@ -47,3 +47,49 @@ grep -cri the tests/inputs > "$OUT_DIR/the.recursive.insensitive.count"
 
 # STDIN, insensitive, count
 cat tests/inputs/*.txt | grep -ci the - > "$OUT_DIR/the.recursive.insensitive.count.stdin"
+
+# Context
+grep -A 1 The $DIR/bustle.txt > "$OUT_DIR/bustle.txt.the.after1"
+grep -B 2 -i nobody $DIR/nobody.txt > "$OUT_DIR/nobody.txt.insensitive.before2"
+grep -C 1 the $DIR/bustle.txt > "$OUT_DIR/bustle.txt.the.context1"
+grep -C 1 -v the $DIR/bustle.txt > "$OUT_DIR/bustle.txt.the.invert.context1"
+grep -C 1 -A 0 How $DIR/nobody.txt > "$OUT_DIR/nobody.txt.how.context1.after0"
+grep -C 1 The $DIR/*.txt > "$OUT_DIR/all.the.capitalized.context1"
+
+# Line numbers, byte offsets, only-matching and filenames
+grep -n the $DIR/bustle.txt > "$OUT_DIR/bustle.txt.the.line_number"
+grep -b the $DIR/bustle.txt > "$OUT_DIR/bustle.txt.the.byte_offset"
+grep -n -C 1 The $DIR/*.txt > "$OUT_DIR/all.the.capitalized.line_number.context1"
+grep -o -b -i "the[a-z]*" $DIR/bustle.txt > "$OUT_DIR/bustle.txt.the.only_matching.byte_offset"
+grep -o -n -i "nobody" $DIR/*.txt > "$OUT_DIR/all.nobody.only_matching.line_number"
+grep -H dog $DIR/fox.txt > "$OUT_DIR/fox.txt.dog.with_filename"
+grep -h -n The $DIR/*.txt > "$OUT_DIR/all.the.capitalized.no_filename"
+grep -l -i the $DIR/*.txt > "$OUT_DIR/all.the.files_with_matches"
+grep -L -i the $DIR/*.txt > "$OUT_DIR/all.the.files_without_match"
+grep -c -H The $DIR/bustle.txt > "$OUT_DIR/bustle.txt.the.capitalized.count.with_filename"
+
+# Colors, from grep's defaults unless GREP_COLORS is given
+unset GREP_COLORS
+grep --color=always -n -C 1 The $DIR/*.txt > "$OUT_DIR/all.the.capitalized.color.line_number.context1"
+grep --color=always -o -b -i "the[a-z]*" $DIR/bustle.txt > "$OUT_DIR/bustle.txt.the.color.only_matching.byte_offset"
+grep --color=always -c -i the $DIR/*.txt > "$OUT_DIR/all.the.color.count"
+grep --color=always -l -i the $DIR/*.txt > "$OUT_DIR/all.the.color.files_with_matches"
+GREP_COLORS="sl=1:cx=2:mt=4:ln=33:rv:ne" grep --color=always -n -v -C 1 the $DIR/bustle.txt > "$OUT_DIR/bustle.txt.the.grep_colors.invert.context1"
+
+# Several patterns, fixed strings, words and lines
+PAT_DIR="tests/patterns"
+grep -e the -e Nobody $DIR/bustle.txt $DIR/nobody.txt > "$OUT_DIR/bustle.nobody.the.nobody.regexp"
+grep -f $PAT_DIR/words.txt $DIR/*.txt > "$OUT_DIR/all.words.file"
+grep -f $PAT_DIR/empty.txt $DIR/*.txt > "$OUT_DIR/all.empty.file"
+grep -F -o -i -f $PAT_DIR/words.txt $DIR/nobody.txt > "$OUT_DIR/nobody.txt.words.fixed_strings.only_matching.insensitive"
+grep -F -o -e . -e , $DIR/bustle.txt > "$OUT_DIR/bustle.txt.dot.comma.fixed_strings.only_matching"
+grep -w the $DIR/bustle.txt > "$OUT_DIR/bustle.txt.the.word_regexp"
+grep -w -o -i "th.*" $DIR/bustle.txt > "$OUT_DIR/bustle.txt.th.word_regexp.only_matching.insensitive"
+grep -F -w -e up -e a $DIR/bustle.txt > "$OUT_DIR/bustle.txt.up.a.fixed_strings.word_regexp"
+grep -x -e Until.eternity. -e And $DIR/bustle.txt > "$OUT_DIR/bustle.txt.until.and.line_regexp"
+grep -F -x -i -e "until eternity." -e "and" $DIR/bustle.txt > "$OUT_DIR/bustle.txt.until.and.fixed_strings.line_regexp.insensitive"
+
+# Max count
+grep -m 1 -A 3 -i the $DIR/bustle.txt > "$OUT_DIR/bustle.txt.the.max_count1.after3.insensitive"
+grep -m 2 -c -i the $DIR/*.txt > "$OUT_DIR/all.the.max_count2.count.insensitive"
+grep -v -m 2 -n the $DIR/bustle.txt > "$OUT_DIR/bustle.txt.the.invert.max_count2.line_number"
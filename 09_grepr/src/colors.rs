@@ -0,0 +1,154 @@
+use std::io::{self, Write};
+
+/// SGR codes for each part of the output, as set by `GREP_COLORS`. An
+/// empty code leaves that part uncolored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Colors {
+    /// Matches in selected lines.
+    pub selected_match: String,
+    /// Matches in context lines.
+    pub context_match: String,
+    /// Whole selected lines.
+    pub selected_line: String,
+    /// Whole context lines.
+    pub context_line: String,
+    pub filename: String,
+    pub line_number: String,
+    pub byte_offset: String,
+    pub separator: String,
+    /// Swap the line colors when `-v` is given.
+    reverse: bool,
+    /// Don't clear to the end of the line after each code.
+    no_erase: bool,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            selected_match: "01;31".to_string(),
+            context_match: "01;31".to_string(),
+            selected_line: String::new(),
+            context_line: String::new(),
+            filename: "35".to_string(),
+            line_number: "32".to_string(),
+            byte_offset: "32".to_string(),
+            separator: "36".to_string(),
+            reverse: false,
+            no_erase: false,
+        }
+    }
+}
+
+impl Colors {
+    /// Reads a `GREP_COLORS` value such as `ms=01;31:fn=35:ne` over the
+    /// defaults. As in grep, unknown or malformed entries are ignored.
+    pub fn parse(spec: &str) -> Self {
+        let mut colors = Self::default();
+        for entry in spec.split(':') {
+            let (name, value) = match entry.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (entry, None),
+            };
+            if value.is_some_and(|value| !value.chars().all(|c| c.is_ascii_digit() || c == ';')) {
+                continue;
+            }
+            match (name, value) {
+                ("mt", Some(value)) => {
+                    colors.selected_match = value.to_string();
+                    colors.context_match = value.to_string();
+                }
+                ("ms", Some(value)) => colors.selected_match = value.to_string(),
+                ("mc", Some(value)) => colors.context_match = value.to_string(),
+                ("sl", Some(value)) => colors.selected_line = value.to_string(),
+                ("cx", Some(value)) => colors.context_line = value.to_string(),
+                ("fn", Some(value)) => colors.filename = value.to_string(),
+                ("ln", Some(value)) => colors.line_number = value.to_string(),
+                ("bn", Some(value)) => colors.byte_offset = value.to_string(),
+                ("se", Some(value)) => colors.separator = value.to_string(),
+                ("rv", None) => colors.reverse = true,
+                ("ne", None) => colors.no_erase = true,
+                _ => {}
+            }
+        }
+        colors
+    }
+
+    /// With `rv`, selected lines are the ones that don't match under `-v`,
+    /// so they take the context color and the other way round.
+    pub fn invert_lines(&mut self, invert_match: bool) {
+        if self.reverse && invert_match {
+            std::mem::swap(&mut self.selected_line, &mut self.context_line);
+        }
+    }
+
+    pub fn start(&self, out: &mut impl Write, sgr: &str) -> io::Result<()> {
+        if !sgr.is_empty() {
+            write!(out, "\x1b[{}m{}", sgr, self.erase())?;
+        }
+        Ok(())
+    }
+
+    pub fn end(&self, out: &mut impl Write, sgr: &str) -> io::Result<()> {
+        if !sgr.is_empty() {
+            write!(out, "\x1b[m{}", self.erase())?;
+        }
+        Ok(())
+    }
+
+    /// Writes `text` in the given color.
+    pub fn paint(&self, out: &mut impl Write, sgr: &str, text: &[u8]) -> io::Result<()> {
+        self.start(out, sgr)?;
+        out.write_all(text)?;
+        self.end(out, sgr)
+    }
+
+    fn erase(&self) -> &'static str {
+        if self.no_erase {
+            ""
+        } else {
+            "\x1b[K"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Colors;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Colors::parse(""), Colors::default());
+
+        let colors = Colors::parse("ms=04:fn=:ln=1;33:sl=7:xx=1:se=red:rv:ne");
+        assert_eq!(colors.selected_match, "04");
+        assert_eq!(colors.context_match, "01;31");
+        assert_eq!(colors.filename, "");
+        assert_eq!(colors.line_number, "1;33");
+        assert_eq!(colors.separator, "36");
+        assert!(colors.reverse);
+        assert!(colors.no_erase);
+
+        let mut colors = colors;
+        colors.invert_lines(true);
+        assert_eq!(colors.selected_line, "");
+        assert_eq!(colors.context_line, "7");
+
+        let colors = Colors::parse("mt=4");
+        assert_eq!(colors.selected_match, "4");
+        assert_eq!(colors.context_match, "4");
+    }
+
+    #[test]
+    fn test_paint() {
+        let mut out = vec![];
+        let colors = Colors::default();
+        colors.paint(&mut out, &colors.filename, b"a.txt").unwrap();
+        colors.paint(&mut out, "", b":").unwrap();
+        assert_eq!(out, b"\x1b[35m\x1b[Ka.txt\x1b[m\x1b[K:");
+
+        let mut out = vec![];
+        let colors = Colors::parse("ne");
+        colors.paint(&mut out, "1", b"x").unwrap();
+        assert_eq!(out, b"\x1b[1mx\x1b[m");
+    }
+}
@@ -1,23 +1,95 @@
 use std::{
+    collections::VecDeque,
     error::Error,
     fs::{self, File},
-    io::{self, BufRead, BufReader},
-    mem,
+    io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, Write},
+    ops::Range,
 };
 
 use clap::{Arg, ArgAction, Command};
-use regex::{Regex, RegexBuilder};
 use walkdir::WalkDir;
 
+mod colors;
+mod matcher;
+mod printer;
+
+use colors::Colors;
+use matcher::{Anchor, MatchOptions, Matcher};
+use printer::{Prefix, Printer};
+
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+/// How many lines to print before and after each matching line.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Context {
+    before: usize,
+    after: usize,
+}
+
+/// A line given to the sink of `find_lines`, without its newline.
+#[derive(Debug)]
+pub struct Line<'a> {
+    number: usize,
+    /// Where the line starts in the file, in bytes.
+    offset: u64,
+    text: &'a [u8],
+    kind: LineKind,
+    matcher: &'a Matcher,
+}
+
+impl Line<'_> {
+    /// Where the pattern matches in the text, skipping empty matches. These
+    /// are only looked for when asked, as most lines are printed whole.
+    fn matches(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.matcher
+            .find_iter(self.text)
+            .filter(|range| !range.is_empty())
+    }
+}
+
+/// Whether a line was selected or is only there as context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineKind {
+    Match,
+    Context,
+}
+
 #[derive(Debug)]
 pub struct Config {
-    pattern: Regex,
+    matcher: Matcher,
     files: Vec<String>,
     recursive: bool,
     count: bool,
     invert_match: bool,
+    context: Context,
+    with_filename: Option<bool>,
+    line_number: bool,
+    byte_offset: bool,
+    only_matching: bool,
+    list_files: Option<ListFiles>,
+    colors: Option<Colors>,
+    binary_files: BinaryFiles,
+    quiet: bool,
+    no_messages: bool,
+    max_count: Option<usize>,
+}
+
+/// What to do with files that have a NUL byte near the start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryFiles {
+    /// Say whether they match instead of printing lines.
+    Binary,
+    /// Search them like any other file.
+    Text,
+    /// Take them to match nothing.
+    WithoutMatch,
+}
+
+/// Which files `-l` and `-L` list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListFiles {
+    WithMatches,
+    WithoutMatch,
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -25,19 +97,63 @@ pub fn get_args() -> MyResult<Config> {
         .version("0.1.0")
         .author("Tian Yu <gasnus@gmail.com>")
         .about("Rust grep")
+        .disable_help_flag(true)
+        .arg(
+            Arg::new("help")
+                .long("help")
+                .help("Print help")
+                .action(ArgAction::Help),
+        )
         .arg(
             Arg::new("pattern")
                 .value_name("PATTERN")
-                .help("Search pattern")
-                .required(true),
+                .help("Search pattern, unless given with -e or -f")
+                .required_unless_present_any(["regexp", "file"]),
         )
         .arg(
             Arg::new("files")
                 .value_name("FILE")
-                .help("Input file(s)")
-                .default_value("-")
+                .help("Input file(s) [default: -]")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("regexp")
+                .value_name("PATTERN")
+                .short('e')
+                .long("regexp")
+                .help("Search for PATTERN; may be given more than once")
+                .allow_hyphen_values(true)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("file")
+                .value_name("FILE")
+                .short('f')
+                .long("file")
+                .help("Read patterns from FILE, one per line")
                 .action(ArgAction::Append),
         )
+        .arg(
+            Arg::new("fixed_strings")
+                .short('F')
+                .long("fixed-strings")
+                .help("Patterns are strings, not regular expressions")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("word_regexp")
+                .short('w')
+                .long("word-regexp")
+                .help("Match only whole words")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("line_regexp")
+                .short('x')
+                .long("line-regexp")
+                .help("Match only whole lines")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("insensitive")
                 .short('i')
@@ -66,65 +182,367 @@ pub fn get_args() -> MyResult<Config> {
                 .help("Invert match")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("after")
+                .value_name("NUM")
+                .short('A')
+                .long("after-context")
+                .help("Print NUM lines of context after each match")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("before")
+                .value_name("NUM")
+                .short('B')
+                .long("before-context")
+                .help("Print NUM lines of context before each match")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("context")
+                .value_name("NUM")
+                .short('C')
+                .long("context")
+                .help("Print NUM lines of context around each match")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("line_number")
+                .short('n')
+                .long("line-number")
+                .help("Print the line number of each line")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("byte_offset")
+                .short('b')
+                .long("byte-offset")
+                .help("Print the byte offset of each line, or of each match with -o")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("only_matching")
+                .short('o')
+                .long("only-matching")
+                .help("Print only the matching parts of lines, one per line")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("with_filename")
+                .short('H')
+                .long("with-filename")
+                .help("Print the filename for each match")
+                .overrides_with("no_filename")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no_filename")
+                .short('h')
+                .long("no-filename")
+                .help("Never print filenames with matches")
+                .overrides_with("with_filename")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("files_with_matches")
+                .short('l')
+                .long("files-with-matches")
+                .help("Print only the names of files with matches")
+                .conflicts_with_all(["count", "files_without_match"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("files_without_match")
+                .short('L')
+                .long("files-without-match")
+                .help("Print only the names of files without matches")
+                .conflicts_with("count")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("color")
+                .value_name("WHEN")
+                .long("color")
+                .visible_alias("colour")
+                .help("Highlight matches, filenames and line numbers")
+                .value_parser(["auto", "always", "never"])
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("auto")
+                .default_value("never"),
+        )
+        .arg(
+            Arg::new("text")
+                .short('a')
+                .long("text")
+                .help("Search binary files as if they were text")
+                .overrides_with("binary_files")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("binary_files")
+                .value_name("TYPE")
+                .long("binary-files")
+                .help("What to do with binary files")
+                .value_parser(["binary", "text", "without-match"])
+                .overrides_with("text")
+                .default_value("binary"),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .visible_alias("silent")
+                .help("Print nothing, and exit 0 at the first match")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no_messages")
+                .short('s')
+                .long("no-messages")
+                .help("Don't print errors about files")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("max_count")
+                .value_name("NUM")
+                .short('m')
+                .long("max-count")
+                .help("Stop reading a file after NUM selected lines")
+                .value_parser(clap::value_parser!(usize)),
+        )
         .get_matches();
 
-    let pattern = matches.get_one::<String>("pattern").unwrap();
-    let pattern = RegexBuilder::new(pattern)
-        .case_insensitive(matches.get_flag("insensitive"))
-        .build()
-        .map_err(|_| format!("Invalid pattern \"{}\"", pattern))?;
+    // Patterns given with newlines in them are several patterns, as in grep.
+    let mut patterns: Vec<String> = matches
+        .get_many::<String>("regexp")
+        .unwrap_or_default()
+        .flat_map(|pattern| pattern.split('\n'))
+        .map(|pattern| pattern.to_owned())
+        .collect();
+    for filename in matches.get_many::<String>("file").unwrap_or_default() {
+        patterns.extend(read_patterns(filename)?);
+    }
 
-    let files = matches
+    let mut files: Vec<String> = matches
         .get_many::<String>("files")
-        .unwrap()
+        .unwrap_or_default()
         .map(|f| f.to_owned())
         .collect();
+    // With -e or -f, the first positional argument is a file.
+    if let Some(first) = matches.get_one::<String>("pattern") {
+        if matches.contains_id("regexp") || matches.contains_id("file") {
+            files.insert(0, first.to_owned());
+        } else {
+            patterns.extend(first.split('\n').map(|pattern| pattern.to_owned()));
+        }
+    }
+    if files.is_empty() {
+        files.push("-".to_string());
+    }
+
+    let anchor = if matches.get_flag("line_regexp") {
+        Anchor::Line
+    } else if matches.get_flag("word_regexp") {
+        Anchor::Word
+    } else {
+        Anchor::None
+    };
+    let matcher = Matcher::new(
+        &patterns,
+        MatchOptions {
+            fixed_strings: matches.get_flag("fixed_strings"),
+            ignore_case: matches.get_flag("insensitive"),
+            anchor,
+        },
+    )?;
 
     let recursive = matches.get_flag("recursive");
     let count = matches.get_flag("count");
     let invert_match = matches.get_flag("invert");
 
+    // -A and -B win over -C, whatever the order they're given in.
+    let lines = |id| {
+        matches
+            .get_one::<usize>(id)
+            .or(matches.get_one("context"))
+            .copied()
+            .unwrap_or(0)
+    };
+    let context = Context {
+        before: lines("before"),
+        after: lines("after"),
+    };
+
+    let with_filename = if matches.get_flag("with_filename") {
+        Some(true)
+    } else if matches.get_flag("no_filename") {
+        Some(false)
+    } else {
+        None
+    };
+
+    let list_files = if matches.get_flag("files_with_matches") {
+        Some(ListFiles::WithMatches)
+    } else if matches.get_flag("files_without_match") {
+        Some(ListFiles::WithoutMatch)
+    } else {
+        None
+    };
+
+    // The codes in GREP_COLORS are read over grep's defaults.
+    let color = match matches.get_one::<String>("color").unwrap().as_str() {
+        "always" => true,
+        "auto" => io::stdout().is_terminal(),
+        _ => false,
+    };
+    let colors = color.then(|| {
+        let mut colors = Colors::parse(&std::env::var("GREP_COLORS").unwrap_or_default());
+        colors.invert_lines(invert_match);
+        colors
+    });
+
+    let binary_files = match matches.get_one::<String>("binary_files").unwrap().as_str() {
+        _ if matches.get_flag("text") => BinaryFiles::Text,
+        "text" => BinaryFiles::Text,
+        "without-match" => BinaryFiles::WithoutMatch,
+        _ => BinaryFiles::Binary,
+    };
+
     Ok(Config {
-        pattern,
+        matcher,
         files,
         recursive,
         count,
         invert_match,
+        context,
+        with_filename,
+        line_number: matches.get_flag("line_number"),
+        byte_offset: matches.get_flag("byte_offset"),
+        only_matching: matches.get_flag("only_matching"),
+        list_files,
+        colors,
+        binary_files,
+        quiet: matches.get_flag("quiet"),
+        no_messages: matches.get_flag("no_messages"),
+        max_count: matches.get_one("max_count").copied(),
     })
 }
 
-pub fn run(config: Config) -> MyResult<()> {
+/// Searches every file and returns grep's exit status: 0 if a line was
+/// selected, 1 if none was and 2 if there was an error. With `--quiet`,
+/// the first selected line ends the search with 0, errors or not.
+pub fn run(config: Config) -> MyResult<i32> {
     let entries = find_files(&config.files, config.recursive);
-    let num_files = entries.len();
-    let print = |fname: &str, val: &str| {
-        if num_files > 1 {
-            print!("{}:{}", fname, val);
+    let prefix = Prefix {
+        filename: config.with_filename.unwrap_or(entries.len() > 1),
+        line_number: config.line_number,
+        byte_offset: config.byte_offset,
+    };
+    // Only whole lines come with context.
+    let context =
+        if config.quiet || config.count || config.only_matching || config.list_files.is_some() {
+            Context::default()
         } else {
-            print!("{}", val);
+            config.context
+        };
+    let mut printer = Printer::new(
+        BufWriter::new(io::stdout()),
+        prefix,
+        config.only_matching,
+        config.colors.clone(),
+        context,
+    );
+
+    let mut selected = false;
+    let mut failed = false;
+    let mut warn = |message: String| {
+        failed = true;
+        if !config.no_messages {
+            eprintln!("{}", message);
         }
     };
 
     for entry in entries {
         match entry {
-            Err(e) => eprintln!("{}", e),
+            Err(e) => warn(e.to_string()),
             Ok(filename) => match open(&filename) {
-                Err(e) => eprintln!("{}: {}", filename, e),
-                Ok(file) => match find_lines(file, &config.pattern, config.invert_match) {
-                    Err(e) => eprintln!("{}", e),
-                    Ok(matches) => {
-                        if config.count {
-                            print(&filename, &format!("{}\n", matches.len()));
-                        } else {
-                            for line in &matches {
-                                print(&filename, line);
-                            }
-                        }
-                    }
+                Err(e) => warn(format!("{}: {}", filename, e)),
+                Ok(file) => match search(file, &filename, &config, context, &mut printer) {
+                    Err(e) => warn(e.to_string()),
+                    Ok(found) => selected |= found,
                 },
             },
         }
+        if selected && config.quiet {
+            return Ok(0);
+        }
     }
-    Ok(())
+    printer.flush()?;
+    Ok(match (failed, selected) {
+        (true, _) => 2,
+        (false, true) => 0,
+        (false, false) => 1,
+    })
+}
+
+/// Searches one file and prints what was asked for, then says whether any
+/// line was selected. With `-q`, `-l` or `-L`, reading stops at the first
+/// selected line.
+///
+/// As in grep, a file is binary if there is a NUL byte in the first block
+/// read. Only whether a binary file matches is printed, not its lines.
+fn search(
+    mut file: impl BufRead,
+    filename: &str,
+    config: &Config,
+    context: Context,
+    printer: &mut Printer<impl Write>,
+) -> MyResult<bool> {
+    printer.start_file();
+    let binary = match config.binary_files {
+        BinaryFiles::Text => false,
+        _ => file.fill_buf()?.contains(&0),
+    };
+    // A binary file that can't match reads as empty.
+    let limit = match config.binary_files {
+        BinaryFiles::WithoutMatch if binary => 0,
+        _ => u64::MAX,
+    };
+    let file = file.take(limit);
+    let query = Query {
+        matcher: &config.matcher,
+        invert_match: config.invert_match,
+        context,
+        max_count: config.max_count,
+    };
+    let selected = match config.list_files {
+        _ if config.quiet => find_lines(file, &query, |_| Ok(false))?,
+        Some(list_files) => {
+            let selected = find_lines(file, &query, |_| Ok(false))?;
+            if (selected > 0) == (list_files == ListFiles::WithMatches) {
+                printer.filename(filename)?;
+            }
+            selected
+        }
+        None if config.count => {
+            let selected = find_lines(file, &query, |_| Ok(true))?;
+            printer.count(filename, selected)?;
+            selected
+        }
+        None if binary => find_lines(file, &query, |line| {
+            if line.kind == LineKind::Match {
+                printer.binary_matches(filename)?;
+                return Ok(false);
+            }
+            Ok(true)
+        })?,
+        None => find_lines(file, &query, |line| {
+            printer.line(filename, line)?;
+            Ok(true)
+        })?,
+    };
+    Ok(selected > 0)
 }
 
 fn find_files(paths: &[String], recursive: bool) -> Vec<MyResult<String>> {
@@ -137,12 +555,14 @@ fn find_files(paths: &[String], recursive: bool) -> Vec<MyResult<String>> {
                 Ok(metadata) => {
                     if metadata.is_dir() {
                         if recursive {
-                            for entry in WalkDir::new(path)
-                                .into_iter()
-                                .flatten()
-                                .filter(|e| e.file_type().is_file())
-                            {
-                                results.push(Ok(entry.path().display().to_string()));
+                            for entry in WalkDir::new(path) {
+                                match entry {
+                                    Ok(entry) if entry.file_type().is_file() => {
+                                        results.push(Ok(entry.path().display().to_string()))
+                                    }
+                                    Ok(_) => {}
+                                    Err(e) => results.push(Err(walk_error(e))),
+                                }
                             }
                         } else {
                             results.push(Err(From::from(format!("{} is a directory", path))));
@@ -159,27 +579,126 @@ fn find_files(paths: &[String], recursive: bool) -> Vec<MyResult<String>> {
     results
 }
 
+/// Names the path walkdir couldn't read, as for a file that couldn't be
+/// opened.
+fn walk_error(err: walkdir::Error) -> Box<dyn Error> {
+    match (err.path(), err.io_error()) {
+        (Some(path), Some(e)) => From::from(format!("{}: {}", path.display(), e)),
+        _ => From::from(err),
+    }
+}
+
+/// Which lines `find_lines` selects, and how many lines around them it
+/// gives as well.
+#[derive(Debug)]
+struct Query<'a> {
+    matcher: &'a Matcher,
+    invert_match: bool,
+    context: Context,
+    /// No more lines are selected after this many, though the context
+    /// after the last one is still given.
+    max_count: Option<usize>,
+}
+
+/// Reads `file` a line at a time and calls `sink` with every selected
+/// line, and with the lines of context around each. Each line is given
+/// once and in order, however the windows of context overlap. Lines before
+/// a match are kept in a ring buffer of `context.before` lines, so nothing
+/// else is held in memory. Reading stops early if `sink` returns false.
+///
+/// Returns the number of selected lines.
 fn find_lines<T: BufRead>(
     mut file: T,
-    pattern: &Regex,
-    invert_match: bool,
-) -> MyResult<Vec<String>> {
-    let mut matches = vec![];
-    let mut line = String::new();
+    query: &Query,
+    mut sink: impl FnMut(&Line) -> MyResult<bool>,
+) -> MyResult<usize> {
+    let Query {
+        matcher,
+        invert_match,
+        context,
+        max_count,
+    } = *query;
+    let mut selected = 0;
+    let mut before: VecDeque<(usize, u64, Vec<u8>)> = VecDeque::with_capacity(context.before);
+    let mut after = 0;
+    let mut offset = 0;
+    let mut line = vec![];
 
-    loop {
-        let bytes = file.read_line(&mut line)?;
+    for number in 1.. {
+        let done = max_count.is_some_and(|max| selected >= max);
+        if done && after == 0 {
+            break;
+        }
+        let bytes = file.read_until(b'\n', &mut line)?;
         if bytes == 0 {
             break;
         }
+        let text = line.strip_suffix(b"\n").unwrap_or(&line);
+
+        let kind = if !done && matcher.is_match(text) ^ invert_match {
+            selected += 1;
+            for (number, offset, text) in before.drain(..) {
+                let line = Line {
+                    number,
+                    offset,
+                    text: &text,
+                    kind: LineKind::Context,
+                    matcher,
+                };
+                if !sink(&line)? {
+                    return Ok(selected);
+                }
+            }
+            after = context.after;
+            Some(LineKind::Match)
+        } else if after > 0 {
+            after -= 1;
+            Some(LineKind::Context)
+        } else {
+            if context.before > 0 {
+                if before.len() == context.before {
+                    before.pop_front();
+                }
+                before.push_back((number, offset, text.to_vec()));
+            }
+            None
+        };
 
-        if pattern.is_match(&line) ^ invert_match {
-            matches.push(mem::take(&mut line));
+        if let Some(kind) = kind {
+            let line = Line {
+                number,
+                offset,
+                text,
+                kind,
+                matcher,
+            };
+            if !sink(&line)? {
+                break;
+            }
         }
+        offset += bytes as u64;
         line.clear();
     }
 
-    Ok(matches)
+    Ok(selected)
+}
+
+/// Reads one pattern from each line of a `-f` file. An empty file has no
+/// patterns, so nothing matches.
+fn read_patterns(filename: &str) -> MyResult<Vec<String>> {
+    let mut contents = String::new();
+    open(filename)
+        .and_then(|mut file| Ok(file.read_to_string(&mut contents)?))
+        .map_err(|e| format!("{}: {}", filename, e))?;
+    if contents.is_empty() {
+        return Ok(vec![]);
+    }
+    Ok(contents
+        .strip_suffix('\n')
+        .unwrap_or(&contents)
+        .split('\n')
+        .map(|pattern| pattern.to_string())
+        .collect())
 }
 
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
@@ -191,41 +710,204 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
 
 #[cfg(test)]
 mod tests {
-    use super::{find_files, find_lines};
+    use super::{find_files, find_lines, Context, LineKind, MatchOptions, Matcher, Query};
     use rand::{distributions::Alphanumeric, Rng};
-    use regex::{Regex, RegexBuilder};
     use std::io::Cursor;
 
+    fn matcher(pattern: &str, ignore_case: bool) -> Matcher {
+        let options = MatchOptions {
+            ignore_case,
+            ..MatchOptions::default()
+        };
+        Matcher::new(&[pattern.to_string()], options).unwrap()
+    }
+
+    /// The numbers and kinds of the lines `find_lines` gives its sink.
+    fn find(
+        text: &[u8],
+        matcher: &Matcher,
+        invert_match: bool,
+        context: Context,
+    ) -> Vec<(usize, LineKind)> {
+        let query = Query {
+            matcher,
+            invert_match,
+            context,
+            max_count: None,
+        };
+        find_query(text, &query)
+    }
+
+    fn find_query(text: &[u8], query: &Query) -> Vec<(usize, LineKind)> {
+        let mut lines = vec![];
+        let count = find_lines(Cursor::new(text), query, |line| {
+            lines.push((line.number, line.kind));
+            Ok(true)
+        })
+        .unwrap();
+        assert_eq!(
+            count,
+            lines
+                .iter()
+                .filter(|(_, kind)| *kind == LineKind::Match)
+                .count()
+        );
+        lines
+    }
+
     #[test]
     fn test_find_lines() {
         let text = b"Lorem\nIpsum\r\nDOLOR";
+        let none = Context::default();
 
         // The pattern _or_ should match the one line, "Lorem"
-        let re1 = Regex::new("or").unwrap();
-        let matches = find_lines(Cursor::new(&text), &re1, false);
-        assert!(matches.is_ok());
-        assert_eq!(matches.unwrap().len(), 1);
+        let re1 = matcher("or", false);
+        let matches = find(text, &re1, false, none);
+        assert_eq!(matches.len(), 1);
 
         // When inverted, the function should match the other two lines
-        let matches = find_lines(Cursor::new(&text), &re1, true);
-        assert!(matches.is_ok());
-        assert_eq!(matches.unwrap().len(), 2);
+        let matches = find(text, &re1, true, none);
+        assert_eq!(matches.len(), 2);
 
         // This regex will be case-insensitive
-        let re2 = RegexBuilder::new("or")
-            .case_insensitive(true)
-            .build()
-            .unwrap();
+        let re2 = matcher("or", true);
 
         // The two lines "Lorem" and "DOLOR" should match
-        let matches = find_lines(Cursor::new(&text), &re2, false);
-        assert!(matches.is_ok());
-        assert_eq!(matches.unwrap().len(), 2);
+        let matches = find(text, &re2, false, none);
+        assert_eq!(matches.len(), 2);
 
         // When inverted, the one remaining line should match
-        let matches = find_lines(Cursor::new(&text), &re2, true);
-        assert!(matches.is_ok());
-        assert_eq!(matches.unwrap().len(), 1);
+        let matches = find(text, &re2, true, none);
+        assert_eq!(matches.len(), 1);
+    }
+
+    #[test]
+    fn test_find_lines_max_count() {
+        use LineKind::{Context as C, Match as M};
+
+        let text = b"x\na\nx\nx\nb\nx\n";
+        let re = matcher("x", false);
+        let query = |invert_match, after, max_count| Query {
+            matcher: &re,
+            invert_match,
+            context: Context { before: 0, after },
+            max_count: Some(max_count),
+        };
+
+        assert_eq!(find_query(text, &query(false, 0, 2)), [(1, M), (3, M)]);
+        assert_eq!(find_query(text, &query(true, 0, 1)), [(2, M)]);
+        assert_eq!(find_query(text, &query(false, 0, 0)), []);
+
+        // Lines after the last one are context, even if they match
+        assert_eq!(
+            find_query(text, &query(false, 2, 2)),
+            [(1, M), (2, C), (3, M), (4, C), (5, C)]
+        );
+    }
+
+    #[test]
+    fn test_find_lines_bytes() {
+        use LineKind::{Context as C, Match as M};
+
+        // Bytes that aren't UTF-8 are matched around, not rejected
+        let text = b"caf\xe9\n\xff\x00\nfoo caf\xc3\xa9\n";
+        assert_eq!(
+            find(text, &matcher("caf", false), false, Context::default()),
+            [(1, M), (3, M)]
+        );
+        assert_eq!(
+            find(
+                text,
+                &matcher("^caf.$", false),
+                false,
+                Context {
+                    before: 1,
+                    after: 0
+                }
+            ),
+            []
+        );
+        assert_eq!(
+            find(
+                text,
+                &matcher("café", false),
+                false,
+                Context {
+                    before: 1,
+                    after: 0
+                }
+            ),
+            [(2, C), (3, M)]
+        );
+    }
+
+    #[test]
+    fn test_find_lines_context() {
+        use LineKind::{Context as C, Match as M};
+
+        let text = b"a\nb\nx\nc\nd\ne\nx\nx\nf\ng\nh\ni\nx\n";
+        let re = matcher("x", false);
+        let context = |before, after| Context { before, after };
+
+        assert_eq!(
+            find(text, &re, false, context(1, 0)),
+            [(2, C), (3, M), (6, C), (7, M), (8, M), (12, C), (13, M)]
+        );
+        assert_eq!(
+            find(text, &re, false, context(0, 1)),
+            [(3, M), (4, C), (7, M), (8, M), (9, C), (13, M)]
+        );
+
+        // Overlapping windows give each line once
+        assert_eq!(
+            find(text, &re, false, context(2, 2)),
+            [
+                (1, C),
+                (2, C),
+                (3, M),
+                (4, C),
+                (5, C),
+                (6, C),
+                (7, M),
+                (8, M),
+                (9, C),
+                (10, C),
+                (11, C),
+                (12, C),
+                (13, M)
+            ]
+        );
+
+        // Context past either end of the file is cut short
+        assert_eq!(find(b"x\na\n", &re, false, context(3, 3)), [(1, M), (2, C)]);
+    }
+
+    #[test]
+    fn test_find_lines_offsets() {
+        let text = b"ab\ncd\r\nab\n";
+        let re = matcher("b$", false);
+        let mut lines = vec![];
+        let query = Query {
+            matcher: &re,
+            invert_match: false,
+            context: Context::default(),
+            max_count: None,
+        };
+        let count = find_lines(Cursor::new(text), &query, |line| {
+            lines.push((line.number, line.offset, line.text.to_vec()));
+            Ok(true)
+        });
+        assert_eq!(count.unwrap(), 2);
+        assert_eq!(lines, [(1, 0, b"ab".to_vec()), (3, 7, b"ab".to_vec())]);
+
+        // The sink can stop the search
+        let mut seen = 0;
+        let count = find_lines(Cursor::new(text), &query, |_| {
+            seen += 1;
+            Ok(false)
+        });
+        assert_eq!(count.unwrap(), 1);
+        assert_eq!(seen, 1);
     }
 
     #[test]
@@ -0,0 +1,251 @@
+use std::{cmp::Reverse, ops::Range, str};
+
+use aho_corasick::{AhoCorasick, Anchored, Input, MatchKind, StartKind};
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::MyResult;
+
+/// What part of a line the patterns have to match, from `-w` and `-x`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    #[default]
+    None,
+    /// Only whole words, with no word character on either side.
+    Word,
+    /// Only the whole line.
+    Line,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MatchOptions {
+    pub fixed_strings: bool,
+    pub ignore_case: bool,
+    pub anchor: Anchor,
+}
+
+/// Finds any of several patterns in a line. Fixed strings are searched for
+/// all at once with Aho-Corasick, which stays fast for thousands of them
+/// where one big regex would be slow to build.
+///
+/// Lines are matched as bytes, so they needn't be UTF-8. Regexes still read
+/// UTF-8 where there is some: `.` matches a whole `é`, but not a stray byte.
+#[derive(Debug)]
+pub enum Matcher {
+    Regex(Regex),
+    Literals {
+        automaton: AhoCorasick,
+        anchor: Anchor,
+    },
+}
+
+impl Matcher {
+    pub fn new(patterns: &[String], options: MatchOptions) -> MyResult<Self> {
+        // Aho-Corasick only folds ASCII case, so other letters go to the
+        // regex engine, escaped.
+        let ascii = patterns.iter().all(|pattern| pattern.is_ascii());
+        if patterns.is_empty() || (options.fixed_strings && (ascii || !options.ignore_case)) {
+            return Self::literals(patterns, options);
+        }
+        let patterns: Vec<_> = patterns
+            .iter()
+            .map(|pattern| match options.fixed_strings {
+                true => regex::escape(pattern),
+                false => pattern.to_string(),
+            })
+            .collect();
+        Self::regex(&patterns, options)
+    }
+
+    fn regex(patterns: &[String], options: MatchOptions) -> MyResult<Self> {
+        let alternation = match patterns {
+            [pattern] => pattern.to_string(),
+            _ => patterns
+                .iter()
+                .map(|pattern| format!("(?:{})", pattern))
+                .collect::<Vec<_>>()
+                .join("|"),
+        };
+        let regex = match options.anchor {
+            Anchor::None => alternation,
+            Anchor::Word => format!(r"\b{{start-half}}(?:{})\b{{end-half}}", alternation),
+            Anchor::Line => format!("^(?:{})$", alternation),
+        };
+        let build = |regex: &str| {
+            RegexBuilder::new(regex)
+                .case_insensitive(options.ignore_case)
+                .build()
+        };
+        match build(&regex) {
+            Ok(regex) => Ok(Self::Regex(regex)),
+            // Name the pattern at fault, if only one is
+            Err(e) => match patterns.iter().find(|pattern| build(pattern).is_err()) {
+                Some(pattern) => Err(From::from(format!("Invalid pattern \"{}\"", pattern))),
+                None => Err(From::from(e.to_string())),
+            },
+        }
+    }
+
+    fn literals(patterns: &[String], options: MatchOptions) -> MyResult<Self> {
+        // Whole words need every match to be seen, as the leftmost one may
+        // not be a word while a shorter or later one is.
+        let (match_kind, start_kind) = match options.anchor {
+            Anchor::None => (MatchKind::LeftmostLongest, StartKind::Unanchored),
+            Anchor::Word => (MatchKind::Standard, StartKind::Unanchored),
+            Anchor::Line => (MatchKind::LeftmostLongest, StartKind::Anchored),
+        };
+        let automaton = AhoCorasick::builder()
+            .ascii_case_insensitive(options.ignore_case)
+            .match_kind(match_kind)
+            .start_kind(start_kind)
+            .build(patterns)?;
+        Ok(Self::Literals {
+            automaton,
+            anchor: options.anchor,
+        })
+    }
+
+    pub fn is_match(&self, text: &[u8]) -> bool {
+        match self {
+            Self::Regex(regex) => regex.is_match(text),
+            Self::Literals {
+                automaton,
+                anchor: Anchor::None,
+            } => automaton.is_match(text),
+            Self::Literals { .. } => self.find_iter(text).next().is_some(),
+        }
+    }
+
+    /// The byte ranges of the leftmost matches in `text` that don't
+    /// overlap, empty ones included.
+    pub fn find_iter<'a>(&'a self, text: &'a [u8]) -> Box<dyn Iterator<Item = Range<usize>> + 'a> {
+        match self {
+            Self::Regex(regex) => Box::new(regex.find_iter(text).map(|m| m.range())),
+            Self::Literals { automaton, anchor } => match anchor {
+                Anchor::None => Box::new(automaton.find_iter(text).map(|m| m.range())),
+                Anchor::Line => Box::new(
+                    automaton
+                        .find(Input::new(text).anchored(Anchored::Yes))
+                        .map(|m| m.range())
+                        .filter(|range| range.end == text.len())
+                        .into_iter(),
+                ),
+                Anchor::Word => {
+                    let mut words: Vec<_> = automaton
+                        .find_overlapping_iter(text)
+                        .map(|m| m.range())
+                        .filter(|range| is_word(text, range))
+                        .collect();
+                    words.sort_by_key(|range| (range.start, Reverse(range.end)));
+                    let mut end = 0;
+                    words.retain(|range| {
+                        let keep = range.start >= end;
+                        if keep {
+                            end = range.end;
+                        }
+                        keep
+                    });
+                    Box::new(words.into_iter())
+                }
+            },
+        }
+    }
+}
+
+/// Whether `range` of `text` has no word character on either side. Bytes
+/// that aren't valid UTF-8 aren't word characters.
+fn is_word(text: &[u8], range: &Range<usize>) -> bool {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let char_at = |bytes: &[u8]| str::from_utf8(bytes).ok().and_then(|s| s.chars().next());
+    let (start, end) = (range.start, range.end);
+    let before = (1..=start.min(4)).find_map(|len| char_at(&text[start - len..start]));
+    let after = (1..=(text.len() - end).min(4)).find_map(|len| char_at(&text[end..end + len]));
+    !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Anchor, MatchOptions, Matcher};
+
+    fn find(patterns: &[&str], options: MatchOptions, text: &str) -> Vec<String> {
+        let patterns: Vec<_> = patterns.iter().map(|p| p.to_string()).collect();
+        let matcher = Matcher::new(&patterns, options).unwrap();
+        let text = text.as_bytes();
+        let found: Vec<_> = matcher
+            .find_iter(text)
+            .map(|range| String::from_utf8_lossy(&text[range]).to_string())
+            .collect();
+        assert_eq!(matcher.is_match(text), !found.is_empty());
+        found
+    }
+
+    #[test]
+    fn test_regex() {
+        let options = MatchOptions::default();
+        assert_eq!(
+            find(&["fo+", "ba."], options, "foo bar baz"),
+            ["foo", "bar", "baz"]
+        );
+        assert!(find(&["x"], options, "foo").is_empty());
+
+        let word = MatchOptions {
+            anchor: Anchor::Word,
+            ..options
+        };
+        assert_eq!(
+            find(&["foo", "b.r"], word, "foobar foo_ foo bar"),
+            ["foo", "bar"]
+        );
+        // A word can start or end with a character that isn't one
+        assert_eq!(find(&["-x"], word, "a -x b-x"), ["-x"]);
+        assert_eq!(find(&["x"], word, "éx x"), ["x"]);
+
+        let line = MatchOptions {
+            anchor: Anchor::Line,
+            ignore_case: true,
+            ..options
+        };
+        assert_eq!(find(&["a", "FOO.*"], line, "foo bar"), ["foo bar"]);
+        assert!(find(&["foo"], line, "foo bar").is_empty());
+
+        let err = Matcher::new(&["ok".to_string(), "*foo".to_string()], options).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid pattern \"*foo\"");
+    }
+
+    #[test]
+    fn test_literals() {
+        let options = MatchOptions {
+            fixed_strings: true,
+            ..MatchOptions::default()
+        };
+        assert_eq!(find(&["a.c", "b"], options, "abc a.c"), ["b", "a.c"]);
+        // The longest of the leftmost matches is taken
+        assert_eq!(find(&["ab", "abcd"], options, "abcde"), ["abcd"]);
+        assert!(find(&["A"], options, "abc").is_empty());
+        assert!(find(&[], options, "abc").is_empty());
+
+        let insensitive = MatchOptions {
+            ignore_case: true,
+            ..options
+        };
+        assert_eq!(find(&["A"], insensitive, "abc"), ["a"]);
+        assert_eq!(find(&["émile"], insensitive, "Émile"), ["Émile"]);
+
+        let word = MatchOptions {
+            anchor: Anchor::Word,
+            ..options
+        };
+        assert_eq!(
+            find(&["ab", "abc", "c"], word, "abc ab abcd c"),
+            ["abc", "ab", "c"]
+        );
+        assert_eq!(find(&["foo bar", "bar"], word, "foo barn bar"), ["bar"]);
+
+        let line = MatchOptions {
+            anchor: Anchor::Line,
+            ..options
+        };
+        assert_eq!(find(&["ab", "abc"], line, "abc"), ["abc"]);
+        assert!(find(&["ab"], line, "abc").is_empty());
+        assert_eq!(find(&[""], line, ""), [""]);
+    }
+}
@@ -0,0 +1,277 @@
+use std::io::{self, Write};
+
+use crate::{colors::Colors, Context, Line, LineKind};
+
+/// What is shown in front of each line, in this order.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Prefix {
+    pub filename: bool,
+    pub line_number: bool,
+    pub byte_offset: bool,
+}
+
+/// Writes lines, counts and filenames in grep's format.
+pub struct Printer<W> {
+    out: W,
+    prefix: Prefix,
+    only_matching: bool,
+    colors: Option<Colors>,
+    groups: Groups,
+}
+
+impl<W: Write> Printer<W> {
+    pub fn new(
+        out: W,
+        prefix: Prefix,
+        only_matching: bool,
+        colors: Option<Colors>,
+        context: Context,
+    ) -> Self {
+        Self {
+            out,
+            prefix,
+            only_matching,
+            colors,
+            groups: Groups::new(context),
+        }
+    }
+
+    pub fn start_file(&mut self) {
+        self.groups.start_file();
+    }
+
+    /// Prints a line found in `filename`. With `--only-matching`, each
+    /// match in a selected line is printed on its own instead, and its byte
+    /// offset is that of the match.
+    pub fn line(&mut self, filename: &str, line: &Line) -> io::Result<()> {
+        if self.groups.separate(line.number) {
+            self.paint(|colors| &colors.separator, b"--")?;
+            self.out.write_all(b"\n")?;
+        }
+        if !self.only_matching {
+            self.write_prefix(filename, line.number, line.offset, line.kind)?;
+            self.write_text(line)?;
+            return self.out.write_all(b"\n");
+        }
+        if line.kind == LineKind::Match {
+            for span in line.matches() {
+                let offset = line.offset + span.start as u64;
+                self.write_prefix(filename, line.number, offset, line.kind)?;
+                self.paint(|colors| &colors.selected_match, &line.text[span])?;
+                self.out.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn count(&mut self, filename: &str, count: usize) -> io::Result<()> {
+        if self.prefix.filename {
+            self.paint(|colors| &colors.filename, filename.as_bytes())?;
+            self.paint(|colors| &colors.separator, b":")?;
+        }
+        writeln!(self.out, "{}", count)
+    }
+
+    /// Says that a binary file matches, in place of its lines.
+    pub fn binary_matches(&mut self, filename: &str) -> io::Result<()> {
+        writeln!(self.out, "Binary file {} matches", filename)
+    }
+
+    /// Prints the name of a file on its own, for `-l` and `-L`.
+    pub fn filename(&mut self, filename: &str) -> io::Result<()> {
+        self.paint(|colors| &colors.filename, filename.as_bytes())?;
+        self.out.write_all(b"\n")
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    fn write_prefix(
+        &mut self,
+        filename: &str,
+        number: usize,
+        offset: u64,
+        kind: LineKind,
+    ) -> io::Result<()> {
+        let separator: &[u8] = match kind {
+            LineKind::Match => b":",
+            LineKind::Context => b"-",
+        };
+        if self.prefix.filename {
+            self.paint(|colors| &colors.filename, filename.as_bytes())?;
+            self.paint(|colors| &colors.separator, separator)?;
+        }
+        if self.prefix.line_number {
+            self.paint(|colors| &colors.line_number, number.to_string().as_bytes())?;
+            self.paint(|colors| &colors.separator, separator)?;
+        }
+        if self.prefix.byte_offset {
+            self.paint(|colors| &colors.byte_offset, offset.to_string().as_bytes())?;
+            self.paint(|colors| &colors.separator, separator)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the text of a line with its matches highlighted, the way
+    /// grep does: the line color is started again after each match and
+    /// only ended after the last of the text.
+    fn write_text(&mut self, line: &Line) -> io::Result<()> {
+        let colors = match &self.colors {
+            Some(colors) => colors,
+            None => return self.out.write_all(line.text),
+        };
+        let (match_color, line_color) = match line.kind {
+            LineKind::Match => (&colors.selected_match, &colors.selected_line),
+            LineKind::Context => (&colors.context_match, &colors.context_line),
+        };
+        let mut end = 0;
+        if !match_color.is_empty() {
+            for span in line.matches() {
+                colors.start(&mut self.out, line_color)?;
+                self.out.write_all(&line.text[end..span.start])?;
+                colors.paint(&mut self.out, match_color, &line.text[span.clone()])?;
+                end = span.end;
+            }
+        }
+        if !line_color.is_empty() && end < line.text.len() {
+            colors.paint(&mut self.out, line_color, &line.text[end..])?;
+            end = line.text.len();
+        }
+        self.out.write_all(&line.text[end..])
+    }
+
+    /// Writes `text` in the color `sgr` picks, if colors are on.
+    fn paint(&mut self, sgr: impl Fn(&Colors) -> &String, text: &[u8]) -> io::Result<()> {
+        match &self.colors {
+            Some(colors) => colors.paint(&mut self.out, sgr(colors), text),
+            None => self.out.write_all(text),
+        }
+    }
+}
+
+/// Puts `--` between groups of lines that aren't next to each other, in
+/// the same file or not, when context was asked for.
+struct Groups {
+    enabled: bool,
+    printed: bool,
+    last: Option<usize>,
+}
+
+impl Groups {
+    fn new(context: Context) -> Self {
+        Self {
+            enabled: context.before > 0 || context.after > 0,
+            printed: false,
+            last: None,
+        }
+    }
+
+    fn start_file(&mut self) {
+        self.last = None;
+    }
+
+    /// Called before printing line `number` of the current file. Returns
+    /// whether a separator goes first.
+    fn separate(&mut self, number: usize) -> bool {
+        let separate =
+            self.enabled && self.printed && self.last.is_none_or(|last| number > last + 1);
+        self.printed = true;
+        self.last = Some(number);
+        separate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Groups, Prefix, Printer};
+    use crate::{
+        colors::Colors,
+        matcher::{MatchOptions, Matcher},
+        Context, Line, LineKind,
+    };
+
+    #[test]
+    fn test_groups() {
+        let mut groups = Groups::new(Context {
+            before: 0,
+            after: 1,
+        });
+        let separated: Vec<_> = [1, 2, 4].map(|number| groups.separate(number)).into();
+        assert_eq!(separated, [false, false, true]);
+        groups.start_file();
+        assert!(groups.separate(5));
+
+        let mut groups = Groups::new(Context::default());
+        assert!(!groups.separate(1));
+        assert!(!groups.separate(3));
+    }
+
+    fn print(printer: &mut Printer<Vec<u8>>, lines: &[(usize, u64, &str, LineKind)]) -> String {
+        let matcher = Matcher::new(&["o+".to_string()], MatchOptions::default()).unwrap();
+        for &(number, offset, text, kind) in lines {
+            let line = Line {
+                number,
+                offset,
+                text: text.as_bytes(),
+                kind,
+                matcher: &matcher,
+            };
+            printer.line("a.txt", &line).unwrap();
+        }
+        String::from_utf8(std::mem::take(&mut printer.out)).unwrap()
+    }
+
+    #[test]
+    fn test_prefix() {
+        let prefix = Prefix {
+            filename: true,
+            line_number: true,
+            byte_offset: true,
+        };
+        let mut printer = Printer::new(vec![], prefix, false, None, Context::default());
+        assert_eq!(
+            print(
+                &mut printer,
+                &[
+                    (3, 12, "foo", LineKind::Match),
+                    (4, 16, "bar", LineKind::Context)
+                ]
+            ),
+            "a.txt:3:12:foo\na.txt-4-16-bar\n"
+        );
+
+        let prefix = Prefix {
+            byte_offset: true,
+            ..Prefix::default()
+        };
+        let mut printer = Printer::new(vec![], prefix, true, None, Context::default());
+        assert_eq!(
+            print(&mut printer, &[(1, 10, "foo boo", LineKind::Match)]),
+            "11:oo\n15:oo\n"
+        );
+    }
+
+    #[test]
+    fn test_colors() {
+        let prefix = Prefix {
+            line_number: true,
+            ..Prefix::default()
+        };
+        let colors = Some(Colors::parse("ne:se=:sl=1"));
+        let mut printer = Printer::new(vec![], prefix, false, colors, Context::default());
+        assert_eq!(
+            print(
+                &mut printer,
+                &[
+                    (1, 0, "foo", LineKind::Match),
+                    (2, 4, "a boo b", LineKind::Match),
+                    (3, 12, "bar", LineKind::Context)
+                ]
+            ),
+            "\x1b[32m1\x1b[m:\x1b[1mf\x1b[01;31moo\x1b[m\n\
+             \x1b[32m2\x1b[m:\x1b[1ma b\x1b[01;31moo\x1b[m\x1b[1m b\x1b[m\n\
+             \x1b[32m3\x1b[m-bar\n"
+        );
+    }
+}
@@ -1,8 +1,9 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
-use std::{fs, path::Path};
+use std::{fs, os::unix::fs::PermissionsExt, path::Path};
 use sys_info::os_type;
+use tempfile::TempDir;
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -12,6 +13,7 @@ const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const NOBODY: &str = "tests/inputs/nobody.txt";
 const INPUTS_DIR: &str = "tests/inputs";
+const WORDS: &str = "tests/patterns/words.txt";
 
 // --------------------------------------------------
 fn gen_bad_file() -> String {
@@ -42,7 +44,7 @@ fn dies_no_args() -> TestResult {
 #[test]
 fn dies_bad_pattern() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&["*foo", FOX])
+        .args(["*foo", FOX])
         .assert()
         .failure()
         .stderr(predicate::str::contains("Invalid pattern \"*foo\""));
@@ -55,7 +57,7 @@ fn warns_bad_file() -> TestResult {
     let bad = gen_bad_file();
     let expected = format!("{}: .* [(]os error 2[)]", bad);
     Command::cargo_bin(PRG)?
-        .args(&["foo", &bad])
+        .args(["foo", &bad])
         .assert()
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
@@ -72,10 +74,11 @@ fn run(args: &[&str], expected_file: &str) -> TestResult {
         expected_file
     };
 
-    let expected = fs::read_to_string(&expected_file)?;
+    let expected = fs::read_to_string(expected_file)?;
 
     Command::cargo_bin(PRG)?
         .args(args)
+        .env_remove("GREP_COLORS")
         .assert()
         .stdout(expected);
     Ok(())
@@ -234,7 +237,7 @@ fn warns_dir_not_recursive() -> TestResult {
     let stdout = "tests/inputs/fox.txt:\
         The quick brown fox jumps over the lazy dog.";
     Command::cargo_bin(PRG)?
-        .args(&["fox", INPUTS_DIR, FOX])
+        .args(["fox", INPUTS_DIR, FOX])
         .assert()
         .stderr(predicate::str::contains("tests/inputs is a directory"))
         .stdout(predicate::str::contains(stdout));
@@ -271,9 +274,693 @@ fn stdin_insensitive_count() -> TestResult {
     let expected = fs::read_to_string(expected_file)?;
 
     Command::cargo_bin(PRG)?
-        .args(&["-ci", "the", "-"])
+        .args(["-ci", "the", "-"])
+        .write_stdin(input)
+        .assert()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn after_context() -> TestResult {
+    run(
+        &["-A", "1", "The", BUSTLE],
+        "tests/expected/bustle.txt.the.after1",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn before_context() -> TestResult {
+    run(
+        &["--before-context", "2", "-i", "nobody", NOBODY],
+        "tests/expected/nobody.txt.insensitive.before2",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn context() -> TestResult {
+    run(
+        &["-C", "1", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.context1",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn context_invert() -> TestResult {
+    run(
+        &["--context", "1", "-v", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.invert.context1",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn context_overridden_by_after() -> TestResult {
+    run(
+        &["-A", "0", "-C", "1", "How", NOBODY],
+        "tests/expected/nobody.txt.how.context1.after0",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn context_multiple_files() -> TestResult {
+    run(
+        &["-C", "1", "The", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.the.capitalized.context1",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_context() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-A", "x", "The", BUSTLE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'x'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn line_number() -> TestResult {
+    run(
+        &["--line-number", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.line_number",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn byte_offset() -> TestResult {
+    run(
+        &["-b", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.byte_offset",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn line_number_context_multiple() -> TestResult {
+    run(
+        &["-n", "-C", "1", "The", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.the.capitalized.line_number.context1",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn only_matching_byte_offset() -> TestResult {
+    run(
+        &["-o", "-b", "-i", "the[a-z]*", BUSTLE],
+        "tests/expected/bustle.txt.the.only_matching.byte_offset",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn only_matching_multiple() -> TestResult {
+    run(
+        &["-o", "-n", "-i", "nobody", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.nobody.only_matching.line_number",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn with_filename() -> TestResult {
+    run(
+        &["-H", "dog", FOX],
+        "tests/expected/fox.txt.dog.with_filename",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn no_filename() -> TestResult {
+    run(
+        &["-h", "-n", "The", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.the.capitalized.no_filename",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn count_with_filename() -> TestResult {
+    run(
+        &["-c", "--with-filename", "The", BUSTLE],
+        "tests/expected/bustle.txt.the.capitalized.count.with_filename",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn files_with_matches() -> TestResult {
+    run(
+        &["-l", "-i", "the", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.the.files_with_matches",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn files_without_match() -> TestResult {
+    run(
+        &["-L", "-i", "the", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.the.files_without_match",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn files_with_matches_stops_reading() -> TestResult {
+    // The rest of the input is never read, so it needn't be text
+    let mut input = b"The first line\n".to_vec();
+    input.extend([0xff; 64 * 1024]);
+    Command::cargo_bin(PRG)?
+        .args(["-l", "The"])
         .write_stdin(input)
         .assert()
+        .success()
+        .stdout("-\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn color_line_number_context() -> TestResult {
+    run(
+        &[
+            "--color=always",
+            "-n",
+            "-C",
+            "1",
+            "The",
+            BUSTLE,
+            EMPTY,
+            FOX,
+            NOBODY,
+        ],
+        "tests/expected/all.the.capitalized.color.line_number.context1",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn color_only_matching() -> TestResult {
+    run(
+        &["--colour=always", "-o", "-b", "-i", "the[a-z]*", BUSTLE],
+        "tests/expected/bustle.txt.the.color.only_matching.byte_offset",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn color_count() -> TestResult {
+    run(
+        &[
+            "--color=always",
+            "-c",
+            "-i",
+            "the",
+            BUSTLE,
+            EMPTY,
+            FOX,
+            NOBODY,
+        ],
+        "tests/expected/all.the.color.count",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn color_files_with_matches() -> TestResult {
+    run(
+        &[
+            "--color=always",
+            "-l",
+            "-i",
+            "the",
+            BUSTLE,
+            EMPTY,
+            FOX,
+            NOBODY,
+        ],
+        "tests/expected/all.the.color.files_with_matches",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn color_never() -> TestResult {
+    run(
+        &["--color=never", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.lowercase",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn color_auto_not_a_terminal() -> TestResult {
+    // Output to a pipe is never colored
+    run(
+        &["--color", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.lowercase",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn grep_colors() -> TestResult {
+    let expected = fs::read_to_string(
+        "tests/expected/bustle.txt.the.grep_colors.invert.context1",
+    )?;
+    Command::cargo_bin(PRG)?
+        .args(["--color=always", "-n", "-v", "-C", "1", "the", BUSTLE])
+        .env("GREP_COLORS", "sl=1:cx=2:mt=4:ln=33:rv:ne")
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_color() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--color=sometimes", "the", BUSTLE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value 'sometimes' for '--color[=<WHEN>]'",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn regexp_multiple() -> TestResult {
+    run(
+        &["-e", "the", "--regexp", "Nobody", BUSTLE, NOBODY],
+        "tests/expected/bustle.nobody.the.nobody.regexp",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn regexp_reads_stdin() -> TestResult {
+    // With -e, no positional argument is needed
+    Command::cargo_bin(PRG)?
+        .args(["-e", "-x"])
+        .write_stdin("a\n-x-\n")
+        .assert()
+        .success()
+        .stdout("-x-\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_regexp() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-e", "foo", "-e", "*foo", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid pattern \"*foo\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn patterns_file() -> TestResult {
+    run(
+        &["-f", WORDS, BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.words.file",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn patterns_file_empty() -> TestResult {
+    run(
+        &[
+            "--file",
+            "tests/patterns/empty.txt",
+            BUSTLE,
+            EMPTY,
+            FOX,
+            NOBODY,
+        ],
+        "tests/expected/all.empty.file",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_patterns_file() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = format!("{}: .* [(]os error 2[)]", bad);
+    Command::cargo_bin(PRG)?
+        .args(["-f", &bad, FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(expected)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_strings_file() -> TestResult {
+    run(
+        &["-F", "-o", "-i", "-f", WORDS, NOBODY],
+        "tests/expected/nobody.txt.words.fixed_strings.only_matching.insensitive",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_strings() -> TestResult {
+    run(
+        &["--fixed-strings", "-o", "-e", ".", "-e", ",", BUSTLE],
+        "tests/expected/bustle.txt.dot.comma.fixed_strings.only_matching",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn word_regexp() -> TestResult {
+    run(
+        &["-w", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.word_regexp",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn word_regexp_only_matching() -> TestResult {
+    run(
+        &["--word-regexp", "-o", "-i", "th.*", BUSTLE],
+        "tests/expected/bustle.txt.th.word_regexp.only_matching.insensitive",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn word_regexp_fixed_strings() -> TestResult {
+    run(
+        &["-F", "-w", "-e", "up", "-e", "a", BUSTLE],
+        "tests/expected/bustle.txt.up.a.fixed_strings.word_regexp",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn line_regexp() -> TestResult {
+    run(
+        &["-x", "-e", "Until.eternity.", "-e", "And", BUSTLE],
+        "tests/expected/bustle.txt.until.and.line_regexp",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn line_regexp_fixed_strings() -> TestResult {
+    run(
+        &[
+            "-F",
+            "--line-regexp",
+            "-i",
+            "-e",
+            "until eternity.",
+            "-e",
+            "and",
+            BUSTLE,
+        ],
+        "tests/expected/bustle.txt.until.and.fixed_strings.line_regexp.insensitive",
+    )
+}
+
+// --------------------------------------------------
+const BINARY: &[u8] = b"foo\nbar\0baz\nfoo again\n";
+
+fn run_binary(args: &[&str], expected: &str) -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .write_stdin(BINARY)
+        .assert()
+        .stdout(expected.to_string());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_matches() -> TestResult {
+    run_binary(&["-n", "-A", "1", "foo"], "Binary file - matches\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_no_match() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg("quux")
+        .write_stdin(BINARY)
+        .assert()
+        .code(1)
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_count() -> TestResult {
+    run_binary(&["-c", "foo"], "2\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_text() -> TestResult {
+    run_binary(&["-a", "foo"], "foo\nfoo again\n")?;
+    run_binary(&["--binary-files=text", "-n", "baz"], "2:bar\0baz\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_without_match() -> TestResult {
+    run_binary(&["--binary-files=without-match", "foo"], "")?;
+    run_binary(&["--binary-files=without-match", "-c", "foo"], "0\n")?;
+    run_binary(&["--binary-files=without-match", "-L", "foo"], "-\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_text_overrides() -> TestResult {
+    // The last of -a and --binary-files wins
+    run_binary(&["--binary-files=without-match", "-a", "-c", "foo"], "2\n")?;
+    run_binary(&["-a", "--binary-files=without-match", "-c", "foo"], "0\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_binary_files() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--binary-files=maybe", "foo"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value 'maybe' for '--binary-files <TYPE>'",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn not_utf8() -> TestResult {
+    // Lines that aren't UTF-8 are searched and printed as they are
+    Command::cargo_bin(PRG)?
+        .args(["-o", "-b", "-i", "FOO"])
+        .write_stdin(b"caf\xe9 foo\nok\n\xff\xfe foo\n".to_vec())
+        .assert()
+        .success()
+        .stdout("5:foo\n15:foo\n");
+    Command::cargo_bin(PRG)?
+        .args(["foo"])
+        .write_stdin(b"caf\xe9 foo\nok\n".to_vec())
+        .assert()
+        .success()
+        .stdout(predicate::eq(b"caf\xe9 foo\n".as_slice()));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exits_one_without_match() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["zzz", BUSTLE, EMPTY])
+        .assert()
+        .code(1)
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exits_two_on_error() -> TestResult {
+    // The other files are still searched
+    let bad = gen_bad_file();
+    let expected =
+        fs::read_to_string("tests/expected/bustle.txt.the.lowercase")?;
+    Command::cargo_bin(PRG)?
+        .args(["-h", "the", &bad, BUSTLE])
+        .assert()
+        .code(2)
         .stdout(expected);
     Ok(())
 }
+
+// --------------------------------------------------
+#[test]
+fn exits_two_on_bad_pattern() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["*foo", FOX])
+        .assert()
+        .code(2);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn quiet() -> TestResult {
+    // The search ends at the first match, so the bad file is never opened
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-q", "the", BUSTLE, &bad])
+        .assert()
+        .code(0)
+        .stdout("")
+        .stderr("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn quiet_after_error() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["--quiet", "the", &bad, BUSTLE])
+        .assert()
+        .code(0)
+        .stdout("")
+        .stderr(predicate::str::contains(bad));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn quiet_without_match() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--silent", "zzz", BUSTLE])
+        .assert()
+        .code(1)
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn no_messages() -> TestResult {
+    let bad = gen_bad_file();
+    let expected =
+        fs::read_to_string("tests/expected/bustle.txt.the.lowercase")?;
+    Command::cargo_bin(PRG)?
+        .args(["-s", "-h", "the", &bad, INPUTS_DIR, BUSTLE])
+        .assert()
+        .code(2)
+        .stdout(expected)
+        .stderr("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_unreadable_dir() -> TestResult {
+    // The rest of the tree is still searched
+    let dir = TempDir::new()?;
+    let locked = dir.path().join("locked");
+    fs::create_dir(&locked)?;
+    fs::write(locked.join("fox.txt"), "the fox\n")?;
+    fs::write(dir.path().join("dog.txt"), "the dog\n")?;
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000))?;
+    // Permissions don't stop root, so there is nothing to test
+    if fs::read_dir(&locked).is_ok() {
+        return Ok(());
+    }
+
+    let root = dir.path().to_str().unwrap();
+    Command::cargo_bin(PRG)?
+        .args(["-r", "the", root])
+        .assert()
+        .code(2)
+        .stdout(format!("{}/dog.txt:the dog\n", root))
+        .stderr(predicate::str::contains("locked: Permission denied"));
+    Command::cargo_bin(PRG)?
+        .args(["-rs", "the", root])
+        .assert()
+        .code(2)
+        .stderr("");
+    // So the TempDir can be removed
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn max_count_after() -> TestResult {
+    run(
+        &["-m", "1", "-A", "3", "-i", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.max_count1.after3.insensitive",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn max_count_count() -> TestResult {
+    run(
+        &[
+            "--max-count",
+            "2",
+            "-c",
+            "-i",
+            "the",
+            BUSTLE,
+            EMPTY,
+            FOX,
+            NOBODY,
+        ],
+        "tests/expected/all.the.max_count2.count.insensitive",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn max_count_invert() -> TestResult {
+    run(
+        &["-v", "-m", "2", "-n", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.invert.max_count2.line_number",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn max_count_zero() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-m", "0", "the", BUSTLE])
+        .assert()
+        .code(1)
+        .stdout("");
+    Ok(())
+}